@@ -0,0 +1,86 @@
+/// The buzzer is a 440Hz square wave, the same tone as sound/440hz.wav.
+pub const BEEP_HZ: u32 = 440;
+pub const BEEP_SAMPLE_RATE: u32 = 44100;
+pub const DEFAULT_BEEP_DUTY: f32 = 0.5;
+
+/// One second of a square wave that is high for the given fraction of each
+/// period. A whole second is generated so it loops without a click.
+pub fn square_wave(duty: f32, frequency: u32, sample_rate: u32) -> Vec<i16> {
+    let amplitude = i16::MAX / 4;
+    (0..sample_rate)
+        .map(|i| {
+            let phase = (i as u64 * frequency as u64 % sample_rate as u64) as f32 / sample_rate as f32;
+            if phase < duty { amplitude } else { -amplitude }
+        })
+        .collect()
+}
+
+/// Writes mono 16-bit samples as a wav file, the only way to hand them to ears.
+pub fn write_wav<P: AsRef<std::path::Path>>(path: P, samples: &[i16], sample_rate: u32) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let data_len = samples.len() as u32 * 2;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len).to_le_bytes())?;
+    file.write_all(b"WAVE")?;
+
+    file.write_all(b"fmt ")?;
+    file.write_all(&16u32.to_le_bytes())?; // Size of the format chunk.
+    file.write_all(&1u16.to_le_bytes())?; // PCM.
+    file.write_all(&1u16.to_le_bytes())?; // Mono.
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&(sample_rate * 2).to_le_bytes())?; // Bytes per second.
+    file.write_all(&2u16.to_le_bytes())?; // Bytes per sample.
+    file.write_all(&16u16.to_le_bytes())?; // Bits per sample.
+
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_is_high_for_the_duty_fraction() {
+        for duty in [0.125, 0.25, 0.5, 0.75] {
+            let samples = square_wave(duty, BEEP_HZ, BEEP_SAMPLE_RATE);
+            assert_eq!(samples.len(), BEEP_SAMPLE_RATE as usize);
+
+            let high = samples.iter().filter(|sample| **sample > 0).count();
+            let low = samples.iter().filter(|sample| **sample < 0).count();
+            assert_eq!(high + low, samples.len());
+            let proportion = high as f32 / samples.len() as f32;
+            assert!((proportion - duty).abs() < 0.01, "{} high at a duty of {}", proportion, duty);
+        }
+    }
+
+    #[test]
+    fn square_wave_has_the_right_number_of_periods() {
+        let samples = square_wave(0.5, BEEP_HZ, BEEP_SAMPLE_RATE);
+        let rising_edges = samples.windows(2).filter(|pair| pair[0] < 0 && pair[1] > 0).count();
+        // The first period starts high, so it has no rising edge.
+        assert_eq!(rising_edges, BEEP_HZ as usize - 1);
+    }
+
+    #[test]
+    fn wav_is_a_header_then_the_samples() {
+        let path = std::env::temp_dir().join(format!("chirp-8-wav-test-{}.wav", std::process::id()));
+        write_wav(&path, &[1, -1, 2], 8000).unwrap();
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(wav.len(), 44 + 6);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]), 8000);
+        assert_eq!(&wav[44..], &[1, 0, 0xFF, 0xFF, 2, 0]);
+    }
+}
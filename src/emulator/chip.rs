@@ -1,27 +1,296 @@
 #![allow(non_snake_case)]
 use std::borrow::BorrowMut;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
 
-use crate::emulator::{Display, Keyboard, Memory, Registers, Timers};
+use crate::emulator::sprites::{FONT_LEN, LARGE_FONT_LEN, LARGE_FONT_OFFSET};
+use crate::emulator::{beep, disasm, ChipRng, DisplaySink, DivergenceReport, Display, InputScript, InputScriptError, Keyboard, Memory, Palette, Quirks, RecordedFrame, Recording, Registers, Resolution, RngKind, RomSettings, RomTooLarge, SaveState, SchipPolicy, ScreenshotError, StateError, TestPattern, Timers, PROGRAM_OFFSET};
 use ears::AudioController;
-use rand::Rng;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChipError {
+    #[error("instruction {1:#06x} at {0:#05x} has an invalid operand")]
+    InvalidOperand(u16, u16),
+    #[error("instruction {1:#06x} at {0:#05x} is a Super Chip-48 instruction, which are turned off")]
+    SchipInstruction(u16, u16),
+    #[error("instruction {1:#06x} at {0:#05x} isn't an instruction")]
+    UnknownInstruction(u16, u16),
+}
+
+#[derive(Debug, Error)]
+pub enum FrameDumpError {
+    #[error("failed to create the frame directory")]
+    Io(#[from] std::io::Error),
+    #[error("failed to save a frame")]
+    Screenshot(#[from] ScreenshotError),
+    #[error("the chip stopped with an error")]
+    Chip(#[from] ChipError),
+}
+
+/// Something suspicious a ROM did that doesn't stop it from running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipWarning {
+    /// The instruction at the first address set the program counter to the
+    /// second, which is below the program.
+    LowJump(u16, u16),
+    /// The Dxyn at the first address read past the end of memory, so only the
+    /// second number of rows were drawn. Only sent with the
+    /// clamp_sprite_reads quirk.
+    SpriteClamped(u16, u8),
+}
+
+impl std::fmt::Display for ChipWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChipWarning::LowJump(from, to) => write!(f, "instruction at {:#05x} jumped below the program to {:#05x}", from, to),
+            ChipWarning::SpriteClamped(at, rows) => write!(f, "sprite drawn at {:#05x} ran past the end of memory, only drawing {} rows", at, rows),
+        }
+    }
+}
+
+/// A timer being set by the ROM, with the value it was set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerEvent {
+    /// Fx15 set the delay timer.
+    DelaySet(u8),
+    /// Fx18 set the sound timer.
+    SoundSet(u8),
+}
+
+/// A read or write of memory by an instruction, logged while memory access
+/// logging is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub value: u8,
+    pub is_write: bool,
+    /// The address of the instruction that made the access.
+    pub pc: u16,
+}
+
+/// Things that happen while running that a front-end may want to know about,
+/// collected until they are taken with poll_event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipEvent {
+    /// The ROM has spent this many frames in a row clearing the screen
+    /// without drawing anything, running the same instructions each time,
+    /// which is what a lot of crashed ROMs look like.
+    SuspectedHang(u32),
+    /// The hash of the display at the end of a frame, with the number of the
+    /// frame, sent when frame hash logging is on.
+    FrameHash(u64, u64),
+    /// The program counter reached a breakpoint at this address, and the
+    /// chip paused before running it.
+    BreakpointHit(u16),
+    /// A frame finished, at this many instructions run, sent once per 60Hz
+    /// tick when frame events are on. Unlike drawing the window, which can
+    /// skip frames, this marks every frame boundary, for recording or keeping
+    /// two chips in sync.
+    FrameAdvanced { cycle: u64, frame: u64 },
+}
+
+#[cfg(feature = "zip")]
+#[derive(Debug, Error)]
+pub enum ZipRomError {
+    #[error("failed to read the zip archive")]
+    Io(#[from] std::io::Error),
+    #[error("failed to open the zip archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("the zip archive has no .ch8 roms")]
+    NoRoms,
+    #[error("the zip archive has no rom named {0}")]
+    MissingEntry(String),
+    #[error("the zip archive has {0} roms, pick one by name")]
+    MultipleRoms(usize),
+    #[error(transparent)]
+    TooLarge(#[from] RomTooLarge),
+}
+
+#[cfg(feature = "flate2")]
+#[derive(Debug, Error)]
+pub enum GzipRomError {
+    #[error("failed to read the gzip rom")]
+    Io(#[from] std::io::Error),
+    #[error("failed to decompress the gzip rom: {0}")]
+    Decompress(std::io::Error),
+    #[error(transparent)]
+    TooLarge(#[from] RomTooLarge),
+}
+
+#[cfg(feature = "flate2")]
+impl From<GzipRomError> for std::io::Error {
+    /// Keeps the gzip error inside, so it can be told apart from a failure to
+    /// read the file with get_ref and downcast_ref.
+    fn from(err: GzipRomError) -> Self {
+        match err {
+            GzipRomError::Io(err) => err,
+            err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// Why a headless run stopped.
+#[derive(Debug)]
+pub enum StopReason {
+    /// The ROM paused itself by jumping to its own address, which is how most
+    /// ROMs end.
+    Halted,
+    Error(ChipError),
+    /// The ROM was still running after the most cycles it was allowed.
+    CycleCapReached,
+    /// The ROM didn't fit in memory, so nothing was run.
+    RomTooLarge(RomTooLarge),
+}
+
+#[derive(Debug)]
+pub struct RunResult {
+    /// The hash of the display when the run stopped.
+    pub hash: u64,
+    pub cycles: usize,
+    pub stop_reason: StopReason,
+}
+
+/// The details of a Dxyn draw, for a debugger to show where sprites land.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawInfo {
+    /// Where the sprite was drawn, before wrapping.
+    pub x: u8,
+    pub y: u8,
+    /// The number of rows in the sprite.
+    pub height: u8,
+    /// The number of pixels that were on and got turned off.
+    pub collided_pixels: u32,
+}
 
-const INSTRUCTIONS_PER_CYCLE: usize = 10;
+/// The instructions and timer ticks counted by strict timing, since it was
+/// turned on or the count last started again.
+#[derive(Debug, Default, Clone, Copy)]
+struct TimingCheck {
+    ticks: u64,
+    instructions: u64,
+}
+
+/// A change made by hand from a debugger, with the value it replaced so it
+/// can be undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Memory(u16, u8),
+    V(u8, u8),
+    I(u16),
+}
+
+/// The timers count down, and the display is drawn, at 60Hz. Each cpu_cycle
+/// is one of these frames.
+const TIMER_HZ: u32 = 60;
+const DEFAULT_CLOCK_HZ: u32 = 600;
+const DEFAULT_INSTRUCTION_HISTORY_LEN: usize = 32;
+/// step_over gives up on a subroutine that hasn't returned after this many
+/// instructions, in case it never does.
+const MAX_STEP_OVER_INSTRUCTIONS: u32 = 1_000_000;
+/// How many edits can be undone.
+const EDIT_HISTORY_LEN: usize = 256;
 
 pub struct Chip {
     memory: Memory,
     registers: Registers,
     keyboard: Keyboard,
     display: Display,
+    /// A copy of the display taken at the end of the last cycle, so what is
+    /// presented doesn't depend on when in the cycle the sprites were drawn.
+    latest_frame: Display,
     timers: Timers,
+    quirks: Quirks,
     sound: Option<ears::Sound>,
     paused: bool,
     waiting_for_key: bool,
     first_instruction: bool,
+    /// The most recently executed (pc, instruction) pairs, oldest first.
+    instruction_history: VecDeque<(u16, u16)>,
+    instruction_history_len: usize,
+    self_test: Option<TestPattern>,
+    log_instructions: bool,
+    clock_hz: u32,
+    /// Instructions owed from previous cycles, in 1/TIMER_HZ of an
+    /// instruction, for clock speeds that aren't a multiple of TIMER_HZ.
+    clock_remainder: u32,
+    speed_multiplier: f32,
+    /// The fraction of a frame owed by the speed multiplier.
+    speed_remainder: f32,
+    strict: bool,
+    startup_delay: Duration,
+    startup_delay_remaining: Duration,
+    strict_timing: Option<TimingCheck>,
+    on_warning: Option<Box<dyn FnMut(ChipWarning)>>,
+    /// Returns which keys are pressed, in the format of pressed_mask.
+    key_source: Option<Box<dyn Fn() -> u16>>,
+    on_timer_set: Option<Box<dyn FnMut(TimerEvent)>>,
+    on_memory_access: Option<Box<dyn FnMut(MemoryAccess)>>,
+    on_collision: Option<Box<dyn FnMut(u8, u8)>>,
+    rng: ChipRng,
+    /// The generator RND uses for the current frame, split from rng at the
+    /// start of every frame while the frame-synced RNG is on.
+    frame_rng: Option<ChipRng>,
+    edit_history: VecDeque<Edit>,
+    /// The palette asked for by the loaded ROM's sidecar file.
+    rom_palette: Option<Palette>,
+    beep_duty: f32,
+    instruction_count: u64,
+    events: VecDeque<ChipEvent>,
+    breakpoints: HashSet<u16>,
+    /// The breakpoint the chip last paused on, which is let through once
+    /// when the chip resumes so it doesn't pause there again.
+    breakpoint_hit: Option<u16>,
+    /// How many identical clear-only frames in a row count as a hang.
+    hang_threshold: Option<u32>,
+    hang_frames: u32,
+    /// A hash of the instructions run in the last frame.
+    last_frame_signature: u64,
+    frame_cleared: bool,
+    frame_drawn: bool,
+    recording: Option<Recording>,
+    input_script: Option<InputScript>,
+    /// How many frames of the input script have been played.
+    input_script_frame: usize,
+    crash_report_path: Option<std::path::PathBuf>,
+    dump_on_exit_path: Option<std::path::PathBuf>,
+    timer_ticks: u64,
+    display_sink: Option<Box<dyn DisplaySink>>,
+    warmup_frames: u32,
+    warmup_hz: u32,
+    warmup_remaining: u32,
+    freeze_timers_when_paused: bool,
+    /// Progress towards the next timer tick while stepping with the timers
+    /// unfrozen, in the same units as clock_remainder.
+    step_remainder: u32,
+    /// How many frames have finished, not counting the startup delay.
+    frame_count: u64,
+    frame_hash_logging: bool,
+    frame_events: bool,
+    /// How many times each mnemonic has run, while counting is on.
+    opcode_histogram: Option<HashMap<&'static str, u64>>,
+    last_draw_info: Option<DrawInfo>,
+    /// The SHA-1 of the loaded ROM, for checking save states against.
+    rom_sha1: [u8; 20],
 }
 
 impl Chip {
+    /// The fastest the clock can be set to, to keep a typo from hanging the
+    /// window.
+    pub const MAX_CLOCK_HZ: u32 = 100_000;
+    /// The slowest and fastest the emulation can be played back at.
+    pub const MIN_SPEED_MULTIPLIER: f32 = 0.1;
+    pub const MAX_SPEED_MULTIPLIER: f32 = 8.0;
+
     pub fn new() -> Self {
-        let sound = match ears::Sound::new("./sound/440hz.wav") {
+        Self::with_quirks(Quirks::new())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        let mut chip = Self::headless(quirks);
+        chip.sound = match ears::Sound::new("./sound/440hz.wav") {
             Ok(mut sound) => {
                 sound.set_looping(true);
                 Some(sound)
@@ -31,32 +300,320 @@ impl Chip {
                 None
             }
         };
+        chip.log_instructions = true;
+        chip
+    }
+
+    /// A chip without sound or instruction logging, for running ROMs with
+    /// nobody watching.
+    pub fn headless(quirks: Quirks) -> Self {
+        // TODO: Command line arguments
+        let display = Display::new(10, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
 
         Self {
             memory: Memory::new(),
             registers: Registers::new(),
             keyboard: Keyboard::new(),
-            // TODO: Command line arguments
-            display: Display::new(10, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0)),
+            latest_frame: display.clone(),
+            display,
             timers: Timers::new(),
-            sound,
+            quirks,
+            sound: None,
             paused: false,
             waiting_for_key: false,
             first_instruction: true,
+            instruction_history: VecDeque::with_capacity(DEFAULT_INSTRUCTION_HISTORY_LEN),
+            instruction_history_len: DEFAULT_INSTRUCTION_HISTORY_LEN,
+            self_test: None,
+            log_instructions: false,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            clock_remainder: 0,
+            speed_multiplier: 1.0,
+            speed_remainder: 0.0,
+            strict: false,
+            startup_delay: Duration::ZERO,
+            startup_delay_remaining: Duration::ZERO,
+            strict_timing: None,
+            on_warning: None,
+            key_source: None,
+            on_timer_set: None,
+            on_memory_access: None,
+            on_collision: None,
+            rng: ChipRng::new(RngKind::Entropy),
+            frame_rng: None,
+            edit_history: VecDeque::new(),
+            rom_palette: None,
+            beep_duty: beep::DEFAULT_BEEP_DUTY,
+            instruction_count: 0,
+            events: VecDeque::new(),
+            breakpoints: HashSet::new(),
+            breakpoint_hit: None,
+            hang_threshold: None,
+            hang_frames: 0,
+            last_frame_signature: 0,
+            frame_cleared: false,
+            frame_drawn: false,
+            recording: None,
+            input_script: None,
+            input_script_frame: 0,
+            crash_report_path: None,
+            dump_on_exit_path: None,
+            timer_ticks: 0,
+            display_sink: None,
+            warmup_frames: 0,
+            warmup_hz: DEFAULT_CLOCK_HZ,
+            warmup_remaining: 0,
+            freeze_timers_when_paused: true,
+            step_remainder: 0,
+            frame_count: 0,
+            frame_hash_logging: false,
+            frame_events: false,
+            opcode_histogram: None,
+            last_draw_info: None,
+            rom_sha1: SaveState::hash_rom(&[]),
         }
     }
 
-    pub fn cpu_cycle(&mut self) {
+    /// Runs a ROM headless for the given number of cycles and returns the hash
+    /// of the resulting display, for regression testing.
+    pub fn run_and_hash(rom_data: &[u8], cycles: usize) -> u64 {
+        Self::run_headless(rom_data, cycles).hash
+    }
+
+    /// Runs a ROM headless until it halts, fails, or has run for max_cycles,
+    /// so a ROM that never ends can't hang the caller.
+    pub fn run_headless(rom_data: &[u8], max_cycles: usize) -> RunResult {
+        let mut chip = Self::headless(Quirks::new());
+        if let Err(err) = chip.load_rom(rom_data) {
+            return RunResult { hash: chip.display.pixels_hash(), cycles: 0, stop_reason: StopReason::RomTooLarge(err) };
+        }
+
+        let mut stop_reason = StopReason::CycleCapReached;
+        let mut cycles = 0;
+        while cycles < max_cycles {
+            cycles += 1;
+            if let Err(err) = chip.run_frame() {
+                stop_reason = StopReason::Error(err);
+                break;
+            }
+            if chip.paused() {
+                stop_reason = StopReason::Halted;
+                break;
+            }
+        }
+
+        RunResult { hash: chip.display.pixels_hash(), cycles, stop_reason }
+    }
+
+    /// Runs each ROM for at most its number of cycles like run_headless. With
+    /// the `rayon` feature, the ROMs are run in parallel.
+    pub fn batch_run(roms: Vec<(&[u8], usize)>) -> Vec<RunResult> {
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            roms.into_par_iter()
+                .map(|(rom_data, cycles)| Self::run_headless(rom_data, cycles))
+                .collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            roms.into_iter()
+                .map(|(rom_data, cycles)| Self::run_headless(rom_data, cycles))
+                .collect()
+        }
+    }
+
+    /// Runs one 60th of a second of real time, which is a frame at normal
+    /// speed and more or fewer with a speed multiplier. Meant to be called by
+    /// a front-end 60 times a second. If an instruction fails, the chip is
+    /// paused and the error returned.
+    pub fn cpu_cycle(&mut self) -> Result<(), ChipError> {
+        self.with_crash_handler(Self::run_scaled_cycles)
+    }
+
+    /// Runs exactly one frame, whatever the speed multiplier is set to. The
+    /// runners that count frames, like run_seconds, use this rather than
+    /// cpu_cycle. If an instruction fails, the chip is paused and the error
+    /// returned.
+    pub fn run_frame(&mut self) -> Result<(), ChipError> {
+        self.with_crash_handler(Self::run_cycle)
+    }
+
+    /// Runs the cycle, writing a crash report first if it panics and a crash
+    /// handler is installed.
+    fn with_crash_handler(&mut self, cycle: fn(&mut Self) -> Result<(), ChipError>) -> Result<(), ChipError> {
+        let path = match self.crash_report_path.clone() {
+            Some(path) => path,
+            None => return cycle(self),
+        };
+
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cycle(self))) {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                match std::fs::write(&path, self.crash_report(&message)) {
+                    Ok(()) => println!("Wrote a crash report to {}", path.display()),
+                    Err(err) => println!("Failed to write a crash report: {}", err),
+                }
+                std::panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Runs as many frames as the speed multiplier owes for one call of
+    /// cpu_cycle, so at 0.5x every other call runs a frame. Whole frames are
+    /// run, leaving the ratio of instructions to timer ticks alone.
+    fn run_scaled_cycles(&mut self) -> Result<(), ChipError> {
+        self.speed_remainder += self.speed_multiplier;
+        while self.speed_remainder >= 1.0 {
+            self.speed_remainder -= 1.0;
+            self.run_cycle()?;
+        }
+        Ok(())
+    }
+
+    /// Writes a crash report to the given file if the emulator panics while
+    /// running a cycle, before carrying on panicking. See crash_report.
+    pub fn install_crash_handler<P: Into<std::path::PathBuf>>(&mut self, path: P) {
+        self.crash_report_path = Some(path.into());
+    }
+
+    /// A report of what the chip was doing, for attaching to bug reports: the
+    /// registers, the instructions around the program counter and the most
+    /// recently executed instructions.
+    pub fn crash_report(&self, message: &str) -> String {
+        use std::fmt::Write;
+
+        let mut report = String::new();
+        let pc = self.registers.pc();
+
+        writeln!(report, "CHIRP-8 crash report").unwrap();
+        writeln!(report, "Panic: {}", message).unwrap();
+        writeln!(report).unwrap();
+
+        self.write_registers(&mut report);
+        writeln!(report).unwrap();
+
+        writeln!(report, "Around PC:").unwrap();
+        let start = pc.saturating_sub(16);
+        let (instructions, position) = disasm::disassemble_range_marked(&self.memory, start, pc.saturating_add(16), pc);
+        for (index, instruction) in instructions.iter().enumerate() {
+            let marker = if position == disasm::PcPosition::At(index) { ">" } else { " " };
+            writeln!(report, "{} {:#05x}: {:04x}  {}", marker, instruction.address, instruction.instruction, instruction.assembly).unwrap();
+        }
+        writeln!(report).unwrap();
+
+        writeln!(report, "Recent instructions, oldest first:").unwrap();
+        for (address, instruction) in &self.instruction_history {
+            writeln!(report, "  {:#05x}: {:04x}  {}", address, instruction, disasm::disassemble(*instruction)).unwrap();
+        }
+
+        report
+    }
+
+    /// Writes the program counter, I, the timers, Vx and the stack.
+    fn write_registers(&self, report: &mut String) {
+        use std::fmt::Write;
+
+        writeln!(report, "PC: {:#05x}  I: {:#05x}  DT: {}  ST: {}", self.registers.pc(), self.registers.i(), self.timers.delay(), self.timers.sound()).unwrap();
+        for x in 0..16 {
+            write!(report, "V{:01x}: {:#04x}  ", x, self.registers.v(x)).unwrap();
+        }
+        writeln!(report).unwrap();
+        writeln!(report, "Stack: {:x?}", self.registers.stack()).unwrap();
+    }
+
+    /// Writes a state dump to the given file when 00FD exits the program, so
+    /// headless runs can save their result. See state_dump.
+    pub fn set_dump_on_exit(&mut self, path: Option<std::path::PathBuf>) {
+        self.dump_on_exit_path = path;
+    }
+
+    /// The registers, followed by all of memory as hex, 16 bytes to a line.
+    pub fn state_dump(&self) -> String {
+        use std::fmt::Write;
+
+        let mut dump = String::new();
+        writeln!(dump, "CHIRP-8 state dump").unwrap();
+        writeln!(dump).unwrap();
+
+        self.write_registers(&mut dump);
+        writeln!(dump).unwrap();
+
+        writeln!(dump, "Memory:").unwrap();
+        for line_start in (0..=self.memory.address_mask() as usize).step_by(16) {
+            write!(dump, "{:#06x}:", line_start).unwrap();
+            for address in line_start..line_start + 16 {
+                write!(dump, " {:02x}", self.memory.read(address as u16)).unwrap();
+            }
+            writeln!(dump).unwrap();
+        }
+
+        dump
+    }
+
+    fn run_cycle(&mut self) -> Result<(), ChipError> {
+        if let Some(pattern) = self.self_test {
+            self.self_test_cycle(pattern);
+            self.latest_frame.clone_from(&self.display);
+            return Ok(());
+        }
+
+        if self.startup_delay_remaining > Duration::ZERO {
+            let frame = Duration::from_secs(1) / TIMER_HZ;
+            self.startup_delay_remaining = self.startup_delay_remaining.saturating_sub(frame);
+            return Ok(());
+        }
+
+        self.play_input_script();
+        if self.frame_rng.is_some() {
+            self.frame_rng = Some(self.rng.split());
+        }
+        let keys = self.keyboard.pressed_mask();
+
         self.first_instruction = true;
+        self.frame_cleared = false;
+        self.frame_drawn = false;
 
-        for _ in 0..INSTRUCTIONS_PER_CYCLE {
+        let cycle_clock_hz = self.current_clock_hz();
+        let instructions_per_cycle = self.next_instructions_per_cycle();
+        let mut instructions_executed = 0;
+
+        for _ in 0..instructions_per_cycle {
+            if !self.paused() && self.check_breakpoint() {
+                self.set_paused(true);
+            }
             if !self.paused() {
-                let instruction = self.fetch_instruction();
-                self.execute_instruction(instruction);
+                instructions_executed += 1;
+                if let Err(err) = self.run_instruction() {
+                    self.set_paused(true);
+                    return Err(err);
+                }
             }
             self.first_instruction = false;
         }
 
+        self.latest_frame.clone_from(&self.display);
+        self.frame_count += 1;
+        if self.frame_hash_logging {
+            self.events.push_back(ChipEvent::FrameHash(self.frame_count, self.display.pixels_hash()));
+        }
+        if self.frame_events {
+            self.events.push_back(ChipEvent::FrameAdvanced { cycle: self.instruction_count, frame: self.frame_count });
+        }
+        if let Some(sink) = self.display_sink.as_mut() {
+            sink.present(&self.latest_frame);
+        }
+        self.check_for_hang(instructions_executed);
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.frames.push(RecordedFrame { keys, display_hash: self.display.pixels_hash() });
+        }
+
         if let Some(sound) = self.sound.borrow_mut() {
             if self.timers.sound() > 0  && !sound.is_playing() {
                 sound.play();
@@ -68,33 +625,729 @@ impl Chip {
         if !self.paused() {
             self.timers.update();
             self.keyboard.update();
+            self.timer_ticks += 1;
+        }
+        self.check_timing(cycle_clock_hz, instructions_executed);
+
+        Ok(())
+    }
+
+    fn run_instruction(&mut self) -> Result<(), ChipError> {
+        let pc = self.registers.pc();
+        let instruction = self.fetch_instruction();
+        self.record_instruction(pc, instruction);
+        self.instruction_count += 1;
+        if let Some(histogram) = self.opcode_histogram.as_mut() {
+            *histogram.entry(disasm::opcode_name(instruction)).or_insert(0) += 1;
+        }
+        self.execute_instruction(instruction)
+    }
+
+    /// Runs a single instruction, even while paused. Sprites are drawn
+    /// straight away rather than waiting for the start of a cycle. See
+    /// set_freeze_timers_when_paused for how this affects the timers.
+    pub fn step_once(&mut self) -> Result<(), ChipError> {
+        self.first_instruction = true;
+        let result = self.run_instruction();
+
+        // Each instruction takes a clock_hz-th of a second, so time catches up
+        // with the timers every clock_hz / TIMER_HZ steps.
+        if !(self.paused && self.freeze_timers_when_paused) {
+            self.step_remainder += TIMER_HZ;
+            if self.step_remainder >= self.clock_hz {
+                self.step_remainder -= self.clock_hz;
+                self.timers.update();
+            }
+        }
+
+        result
+    }
+
+    /// Steps like step_once, except that a 2nnn call runs until the
+    /// subroutine returns, stopping early at a breakpoint. The chip is paused
+    /// if a breakpoint stops it.
+    pub fn step_over(&mut self) -> Result<(), ChipError> {
+        let instruction = self.next_instruction();
+        let depth = self.registers.stack().len();
+
+        self.step_once()?;
+        if instruction & 0xF000 != 0x2000 {
+            return Ok(());
+        }
+
+        for _ in 0..MAX_STEP_OVER_INSTRUCTIONS {
+            if self.registers.stack().len() <= depth {
+                break;
+            }
+            if self.check_breakpoint() {
+                self.set_paused(true);
+                break;
+            }
+            self.step_once()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether stepping through instructions while paused leaves the timers
+    /// alone, so inspecting the chip doesn't let time pass. Defaults to true.
+    /// Either way, step_frame ticks the timers once.
+    pub fn set_freeze_timers_when_paused(&mut self, freeze_timers_when_paused: bool) {
+        self.freeze_timers_when_paused = freeze_timers_when_paused;
+    }
+
+    /// Runs one cycle, a 60th of a second's worth of instructions and one tick
+    /// of the timers, even while paused. The chip is left paused if it was
+    /// before.
+    pub fn step_frame(&mut self) -> Result<(), ChipError> {
+        let paused = self.paused;
+        self.paused = false;
+        let result = self.run_frame();
+        self.paused |= paused;
+        result
+    }
+
+    /// How much time has passed inside the emulator, counted in ticks of the
+    /// 60Hz timers. Time doesn't pass while paused, or before the startup
+    /// delay is over.
+    pub fn emulated_time(&self) -> Duration {
+        Duration::from_secs(self.timer_ticks) / TIMER_HZ
+    }
+
+    /// Starts or stops counting how many times each kind of instruction runs,
+    /// for profiling ROMs. Off by default, as it slows down every instruction.
+    /// Turning it on starts the counts from zero.
+    pub fn set_opcode_histogram(&mut self, enabled: bool) {
+        self.opcode_histogram = if enabled { Some(HashMap::new()) } else { None };
+    }
+
+    /// How many times each mnemonic, as named by disasm::opcode_name, has run
+    /// since counting was turned on.
+    pub fn opcode_histogram(&self) -> HashMap<&'static str, u64> {
+        self.opcode_histogram.clone().unwrap_or_default()
+    }
+
+    /// The details of the most recent Dxyn, or None if nothing has been drawn
+    /// since the ROM was loaded.
+    pub fn last_draw_info(&self) -> Option<DrawInfo> {
+        self.last_draw_info
+    }
+
+    /// How many cycles have run to the end without the chip being paused.
+    pub fn cycles(&self) -> u64 {
+        self.timer_ticks
+    }
+
+    /// How many instructions have run since the chip was made.
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    /// Starts recording the keys pressed and the display every frame. The
+    /// loaded ROM is taken from memory, so this should be called before the
+    /// ROM starts running.
+    pub fn start_recording(&mut self) {
+        let rom = (0..self.memory.rom_len())
+            .map(|i| self.memory.read(PROGRAM_OFFSET.wrapping_add(i as u16)))
+            .collect();
+        self.recording = Some(Recording { rom, quirks: self.quirks, clock_hz: self.clock_hz, frames: Vec::new() });
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// Plays an input script back from the next frame, holding down its keys
+    /// in place of the keyboard until it runs out. See InputScript.
+    pub fn load_input_script<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), InputScriptError> {
+        self.set_input_script(Some(InputScript::from_file(path)?));
+        Ok(())
+    }
+
+    pub fn set_input_script(&mut self, input_script: Option<InputScript>) {
+        self.input_script = input_script;
+        self.input_script_frame = 0;
+    }
+
+    /// Whether an input script is still being played.
+    pub fn playing_input_script(&self) -> bool {
+        self.input_script.is_some()
+    }
+
+    fn play_input_script(&mut self) {
+        let keys = match self.input_script.as_ref() {
+            Some(input_script) => input_script.keys(self.input_script_frame),
+            None => return,
+        };
+        match keys {
+            Some(keys) => {
+                self.keyboard.set_pressed_mask(keys);
+                self.input_script_frame += 1;
+            }
+            // The keys are left as the script's last frame had them.
+            None => self.input_script = None,
+        }
+    }
+
+    /// Resets the chip, loads the recording's ROM and plays it back, pressing the recorded keys
+    /// each frame. Returns the first frame where the display doesn't match
+    /// what was recorded, if there is one. ROMs that use RND can only be
+    /// verified if they were recorded with a seeded set_rng, and the same one
+    /// is set again before replaying.
+    pub fn replay_with_verification(&mut self, recording: &Recording) -> Option<DivergenceReport> {
+        self.quirks = recording.quirks;
+        self.set_clock_hz(recording.clock_hz);
+        self.reset();
+        // The ROM was taken from memory, so it only fails to fit if memory
+        // has been made smaller since, and the replay goes wrong straight
+        // away.
+        if self.load_rom(&recording.rom).is_err() {
+            let expected_hash = recording.frames.first()?.display_hash;
+            return Some(DivergenceReport { frame: 0, expected_hash, actual_hash: self.display.pixels_hash() });
+        }
+        // Frames spent in the startup delay aren't recorded.
+        self.startup_delay_remaining = Duration::ZERO;
+
+        for (frame, recorded) in recording.frames.iter().enumerate() {
+            self.keyboard.set_pressed_mask(recorded.keys);
+            // An error pauses the chip, which shows up as a divergence soon
+            // enough if it wasn't in the recording.
+            let _ = self.run_frame();
+
+            let actual_hash = self.display.pixels_hash();
+            if actual_hash != recorded.display_hash {
+                return Some(DivergenceReport { frame, expected_hash: recorded.display_hash, actual_hash });
+            }
+        }
+        None
+    }
+
+    /// The SHA-1 of the loaded ROM, which save states are checked against.
+    pub fn rom_sha1(&self) -> [u8; 20] {
+        self.rom_sha1
+    }
+
+    /// The memory, registers, timers and display as they are now, to go back
+    /// to later with load_state.
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            rom_sha1: self.rom_sha1,
+            memory: self.memory.clone(),
+            registers: self.registers.clone(),
+            timers: self.timers.clone(),
+            display: self.display.clone(),
+            waiting_for_key: self.waiting_for_key,
+        }
+    }
+
+    /// Goes back to a state from save_state. The state has to have been saved
+    /// with the same ROM loaded, or it is left unloaded and
+    /// StateError::RomMismatch is returned.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), StateError> {
+        if state.rom_sha1 != self.rom_sha1 {
+            return Err(StateError::RomMismatch);
+        }
+
+        self.memory.clone_from(&state.memory);
+        self.registers.clone_from(&state.registers);
+        self.timers.clone_from(&state.timers);
+        self.display.clone_from(&state.display);
+        self.latest_frame.clone_from(&self.display);
+        self.waiting_for_key = state.waiting_for_key;
+        self.last_draw_info = None;
+        Ok(())
+    }
+
+    /// The registers, timers and display as pretty-printed JSON, for reading
+    /// while debugging. The display is one string per row, with `#` for the
+    /// pixels that are on. This can't be loaded back in.
+    #[cfg(feature = "serde_json")]
+    pub fn state_json(&self) -> String {
+        let v: Vec<u8> = (0..16).map(|x| self.registers.v(x)).collect();
+        let state = serde_json::json!({
+            "registers": {
+                "v": v,
+                "i": self.registers.i(),
+                "pc": self.registers.pc(),
+                "stack": self.registers.stack(),
+            },
+            "timers": {
+                "delay": self.timers.delay(),
+                "sound": self.timers.sound(),
+            },
+            "display": {
+                "width": self.display.width(),
+                "height": self.display.height(),
+                "rows": self.display.to_ascii('#', '.').lines().collect::<Vec<_>>(),
+            },
+        });
+        serde_json::to_string_pretty(&state).unwrap()
+    }
+
+    /// Takes the oldest event that hasn't been taken yet.
+    pub fn poll_event(&mut self) -> Option<ChipEvent> {
+        self.events.pop_front()
+    }
+
+    /// Pauses the chip, and sends a BreakpointHit event, before running the
+    /// instruction at the given address. Resuming runs it as normal.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<u16> {
+        &self.breakpoints
+    }
+
+    /// Whether the program counter is on a breakpoint that should pause the
+    /// chip, sending the event if it is.
+    fn check_breakpoint(&mut self) -> bool {
+        let pc = self.registers.pc();
+        if self.breakpoint_hit.take() == Some(pc) || !self.breakpoints.contains(&pc) {
+            return false;
+        }
+
+        self.breakpoint_hit = Some(pc);
+        self.events.push_back(ChipEvent::BreakpointHit(pc));
+        true
+    }
+
+    /// Sends a SuspectedHang event once the ROM has cleared the screen without
+    /// drawing, running the same instructions, for this many frames in a row.
+    /// Off by default.
+    pub fn set_hang_threshold(&mut self, frames: Option<u32>) {
+        self.hang_threshold = frames;
+        self.hang_frames = 0;
+    }
+
+    /// Sends a FrameHash event at the end of every frame, making a trace that
+    /// can be compared against another emulator's to find where they differ.
+    pub fn set_frame_hash_logging(&mut self, frame_hash_logging: bool) {
+        self.frame_hash_logging = frame_hash_logging;
+    }
+
+    /// Sends a FrameAdvanced event at the end of every frame. Off by default,
+    /// so the events don't pile up when nothing polls for them.
+    pub fn set_frame_events(&mut self, frame_events: bool) {
+        self.frame_events = frame_events;
+    }
+
+    fn check_for_hang(&mut self, instructions_executed: u32) {
+        let threshold = match self.hang_threshold {
+            Some(threshold) => threshold,
+            None => return,
+        };
+
+        if !self.frame_cleared || self.frame_drawn {
+            self.hang_frames = 0;
+            return;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        let skip = self.instruction_history.len().saturating_sub(instructions_executed as usize);
+        for instruction in self.instruction_history.iter().skip(skip) {
+            instruction.hash(&mut hasher);
+        }
+        let signature = hasher.finish();
+
+        if signature == self.last_frame_signature {
+            self.hang_frames += 1;
+        } else {
+            self.hang_frames = 1;
+        }
+        self.last_frame_signature = signature;
+
+        if self.hang_frames == threshold {
+            self.events.push_back(ChipEvent::SuspectedHang(self.hang_frames));
+        }
+    }
+
+    /// Runs for the given amount of emulated time as fast as possible, rather
+    /// than waiting for real time to pass. The timers tick once per 1/60th of
+    /// a second.
+    pub fn run_seconds(&mut self, seconds: f64) -> Result<(), ChipError> {
+        let cycles = (seconds * TIMER_HZ as f64).round() as u64;
+        for _ in 0..cycles {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Runs for the given number of frames as fast as possible, saving each
+    /// finished frame to the directory as a png, from `frame-00000.png` up,
+    /// for looking through later or making into a video. The directory is
+    /// made if it doesn't exist. Frames are drawn in the ROM's palette if its
+    /// settings have one.
+    pub fn dump_frames_to<P: AsRef<std::path::Path>>(&mut self, dir: P, count: usize) -> Result<(), FrameDumpError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let palette = self.rom_palette.unwrap_or(Palette::WHITE);
+
+        for frame in 0..count {
+            self.run_frame()?;
+            self.latest_frame.save_png(dir.join(format!("frame-{:05}.png", frame)), &palette)?;
+        }
+        Ok(())
+    }
+
+    /// Cycles through test patterns on the display before running the ROM, so
+    /// colors and scaling can be checked. Any key advances to the next
+    /// pattern.
+    pub fn self_test(&mut self) {
+        self.self_test = Some(TestPattern::Solid);
+        self.display.draw_test_pattern(TestPattern::Solid);
+    }
+
+    pub fn is_self_testing(&self) -> bool {
+        self.self_test.is_some()
+    }
+
+    fn self_test_cycle(&mut self, pattern: TestPattern) {
+        if self.keyboard.just_released().is_some() {
+            self.self_test = pattern.next();
+            match self.self_test {
+                Some(pattern) => self.display.draw_test_pattern(pattern),
+                None => self.display.clear(),
+            }
+        }
+        self.keyboard.update();
+    }
+
+    /// Puts the machine back how it was when it was made: the registers,
+    /// stack, timers, display, keys and program memory are cleared, and the
+    /// cycle and instruction counts go back to 0. Settings such as the
+    /// quirks, clock speed and breakpoints are kept.
+    pub fn reset(&mut self) {
+        self.memory.clear_program();
+        self.registers = Registers::new();
+        self.timers = Timers::new();
+        self.display.set_resolution(Resolution::Low);
+        self.display.select_planes(1);
+        self.latest_frame.clone_from(&self.display);
+        self.keyboard.set_pressed_mask(0);
+        self.keyboard.update();
+        self.paused = false;
+        self.waiting_for_key = false;
+        self.first_instruction = true;
+        self.instruction_history.clear();
+        self.clock_remainder = 0;
+        self.speed_remainder = 0.0;
+        self.step_remainder = 0;
+        self.instruction_count = 0;
+        self.timer_ticks = 0;
+        self.frame_count = 0;
+        self.events.clear();
+        self.breakpoint_hit = None;
+        self.hang_frames = 0;
+        self.last_frame_signature = 0;
+        self.edit_history.clear();
+        self.last_draw_info = None;
+    }
+
+    /// Copies a ROM into memory at the program start. ROMs too large to fit
+    /// are rejected, leaving memory as it was.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), RomTooLarge> {
+        self.memory.load_rom(rom_data)?;
+        self.rom_sha1 = SaveState::hash_rom(rom_data);
+        self.startup_delay_remaining = self.startup_delay;
+        self.warmup_remaining = self.warmup_frames;
+        self.last_draw_info = None;
+        Ok(())
+    }
+
+    /// Changes the timbre of the beep by setting the fraction of the square
+    /// wave that is high, e.g. 0.125, 0.25 or 0.5. Defaults to 0.5.
+    pub fn set_beep_duty(&mut self, duty: f32) {
+        self.beep_duty = duty.clamp(0.0, 1.0);
+
+        // Headless chips have no sound to replace.
+        if self.sound.is_none() {
+            return;
         }
 
+        // ears can only load sounds from files, so the beep is written to the
+        // same file every time and removed once it has been read.
+        let path = std::env::temp_dir().join(format!("chirp-8-beep-{}.wav", std::process::id()));
+        let samples = beep::square_wave(self.beep_duty, beep::BEEP_HZ, beep::BEEP_SAMPLE_RATE);
+        if let Err(err) = beep::write_wav(&path, &samples, beep::BEEP_SAMPLE_RATE) {
+            println!("Failed to write the beep: {}", err);
+            return;
+        }
+
+        let sound = ears::Sound::new(&path.to_string_lossy());
+        let _ = std::fs::remove_file(&path);
+        match sound {
+            Ok(mut sound) => {
+                sound.set_looping(true);
+                self.sound = Some(sound);
+            },
+            Err(err) => println!("{}", err),
+        }
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        self.memory.load_rom(rom_data);
+    pub fn beep_duty(&self) -> f32 {
+        self.beep_duty
     }
 
+    /// Resets the chip and loads a ROM without running it, paused on its
+    /// first instruction, so breakpoints can be set before anything runs.
+    pub fn load_rom_paused(&mut self, rom_data: &[u8]) -> Result<(), RomTooLarge> {
+        self.reset();
+        self.load_rom(rom_data)?;
+        self.set_paused(true);
+        Ok(())
+    }
+
+    /// Holds off running a ROM for a while after it is loaded, so the game
+    /// doesn't start the instant the window appears.
+    pub fn set_startup_delay(&mut self, startup_delay: Duration) {
+        self.startup_delay = startup_delay;
+    }
+
+    /// Reads a whole ROM from a reader, such as stdin, and loads it. ROMs too
+    /// large to fit in memory are rejected rather than cut short.
+    pub fn load_rom_from_reader<R: std::io::Read>(&mut self, mut reader: R) -> std::io::Result<()> {
+        let mut rom_data = Vec::new();
+        reader.read_to_end(&mut rom_data)?;
+        self.load_rom(&rom_data)?;
+        Ok(())
+    }
+
+    /// Reads a gzip compressed ROM from a reader, and loads it like
+    /// load_rom_from_reader.
+    #[cfg(feature = "flate2")]
+    pub fn load_rom_from_gzip_reader<R: std::io::Read>(&mut self, reader: R) -> Result<(), GzipRomError> {
+        let rom_data = gunzip(reader, self.memory.max_rom_len())?;
+        self.load_rom(&rom_data)?;
+        Ok(())
+    }
+
+    /// Loads a ROM file, along with its settings if it has a sidecar file.
+    /// With the flate2 feature, files ending in `.gz` are decompressed first,
+    /// and failing to decompress one is a GzipRomError inside the io error.
+    /// See RomSettings.
     pub fn load_rom_from_path(&mut self, path: &std::path::Path) -> std::io::Result<()> {
         let mut file = std::fs::File::open(path)?;
-        let mut rom_data = Vec::new();
 
+        #[cfg(feature = "flate2")]
+        if path.extension() == Some(std::ffi::OsStr::new("gz")) {
+            self.load_rom_from_gzip_reader(file)?;
+            return self.load_sidecar(path);
+        }
+
+        let mut rom_data = Vec::new();
         std::io::Read::read_to_end(&mut file, &mut rom_data)?;
+        self.load_rom(&rom_data)?;
 
-        self.load_rom(&rom_data);
+        self.load_sidecar(path)
+    }
 
+    fn load_sidecar(&mut self, rom_path: &std::path::Path) -> std::io::Result<()> {
+        if let Some(settings) = RomSettings::load_sidecar(rom_path)? {
+            self.apply_settings(&settings);
+        }
         Ok(())
     }
 
+    pub fn apply_settings(&mut self, settings: &RomSettings) {
+        if let Some(quirks) = settings.quirks {
+            self.quirks = quirks;
+        }
+        if let Some(clock_hz) = settings.clock_hz {
+            self.set_clock_hz(clock_hz);
+        }
+        if let Some(name) = &settings.palette {
+            self.rom_palette = Palette::preset(name);
+            if self.rom_palette.is_none() {
+                println!("Unknown palette in ROM settings: {}", name);
+            }
+        }
+    }
+
+    /// The palette the loaded ROM's settings asked for, if any.
+    pub fn rom_palette(&self) -> Option<Palette> {
+        self.rom_palette
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Changes the quirks from the next instruction on, e.g. after editing
+    /// them in a front-end. Quirks can be saved in the same form as the
+    /// `[quirks]` table of a sidecar file.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Loads a ROM out of a zip archive. Without an entry name, the archive
+    /// has to hold exactly one .ch8 file.
+    #[cfg(feature = "zip")]
+    pub fn load_rom_from_zip<P: AsRef<std::path::Path>>(&mut self, path: P, entry: Option<&str>) -> Result<(), ZipRomError> {
+        let file = std::fs::File::open(path)?;
+        self.load_rom_from_zip_reader(file, entry)
+    }
+
+    /// Like load_rom_from_zip, for archives that aren't in a file.
+    #[cfg(feature = "zip")]
+    pub fn load_rom_from_zip_reader<R: std::io::Read + std::io::Seek>(&mut self, reader: R, entry: Option<&str>) -> Result<(), ZipRomError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+
+        let roms: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.to_lowercase().ends_with(".ch8"))
+            .map(String::from)
+            .collect();
+
+        let name = match entry {
+            Some(entry) => match archive.file_names().find(|name| *name == entry) {
+                Some(name) => name.to_string(),
+                None => return Err(ZipRomError::MissingEntry(entry.to_string())),
+            },
+            None => match roms.as_slice() {
+                [] => return Err(ZipRomError::NoRoms),
+                [name] => name.clone(),
+                _ => return Err(ZipRomError::MultipleRoms(roms.len())),
+            },
+        };
+
+        let mut rom_data = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name(&name)?, &mut rom_data)?;
+
+        self.load_rom(&rom_data)?;
+
+        Ok(())
+    }
+
+    /// Writes a listing of the loaded ROM's instructions to a file, one
+    /// instruction per line with its address and bytes.
+    pub fn export_disassembly<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let end = PROGRAM_OFFSET as usize + self.memory.rom_len();
+        for instruction in disasm::disassemble_range(&self.memory, PROGRAM_OFFSET, end.min(u16::MAX as usize) as u16) {
+            writeln!(
+                file,
+                "{:#05x}: {:02x} {:02x}  {}",
+                instruction.address,
+                instruction.instruction >> 8,
+                instruction.instruction & 0x00FF,
+                instruction.assembly
+            )?;
+        }
+        file.flush()
+    }
+
     pub fn process_input(&mut self, input: glutin::event::KeyboardInput) {
         self.keyboard.process_input(input);
     }
 
+    /// Whether the sound timer is running, and the buzzer sounding.
+    pub fn sound_active(&self) -> bool {
+        self.timers.sound() > 0
+    }
+
+    /// Whether execution is blocked on an Fx0A instruction waiting for a key.
+    pub fn waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    /// The registers, including the stack of return addresses, for a debugger
+    /// to show.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Writes a byte of memory from a debugger. Unlike writing through
+    /// memory_mut, this can be undone with undo_edit.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.record_edit(Edit::Memory(address, self.memory.read(address)));
+        self.memory.write(address, value);
+    }
+
+    /// Sets Vx from a debugger, so that it can be undone with undo_edit.
+    pub fn edit_v(&mut self, x: u8, value: u8) {
+        self.record_edit(Edit::V(x, self.registers.v(x)));
+        self.registers.set_v(x, value);
+    }
+
+    /// Sets I from a debugger, so that it can be undone with undo_edit.
+    pub fn edit_i(&mut self, value: u16) {
+        self.record_edit(Edit::I(self.registers.i()));
+        self.registers.set_i(value);
+    }
+
+    /// Puts back the value replaced by the most recent poke, edit_v or
+    /// edit_i. Up to EDIT_HISTORY_LEN edits are kept. Returns false if there
+    /// was nothing to undo.
+    pub fn undo_edit(&mut self) -> bool {
+        match self.edit_history.pop_back() {
+            Some(Edit::Memory(address, value)) => self.memory.write(address, value),
+            Some(Edit::V(x, value)) => self.registers.set_v(x, value),
+            Some(Edit::I(value)) => self.registers.set_i(value),
+            None => return false,
+        }
+        true
+    }
+
+    fn record_edit(&mut self, edit: Edit) {
+        if self.edit_history.len() == EDIT_HISTORY_LEN {
+            self.edit_history.pop_front();
+        }
+        self.edit_history.push_back(edit);
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// For poking values into memory, such as options some test ROMs read.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn keyboard(&self) -> &Keyboard {
+        &self.keyboard
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
     pub fn display(&self) -> &Display {
         &self.display
     }
 
+    /// The mask of XO-CHIP planes that draws and clears target, as set by
+    /// Fn01. Only the first plane is selected until a ROM changes it.
+    pub fn selected_planes(&self) -> u8 {
+        self.display.selected_planes()
+    }
+
+    /// The display as it was at the end of the last cycle, which is what
+    /// should be presented. Unlike display, this never shows a frame that is
+    /// only partly drawn.
+    pub fn latest_frame(&self) -> &Display {
+        &self.latest_frame
+    }
+
+    /// Sends every finished frame to the sink, at the end of each cycle.
+    pub fn set_display_sink(&mut self, sink: Box<dyn DisplaySink>) {
+        self.display_sink = Some(sink);
+    }
+
+    /// Blanks the screen without touching the rest of the chip, unlike 00E0
+    /// this doesn't run as an instruction. Both XO-CHIP planes are cleared.
+    pub fn clear_display(&mut self) {
+        self.display.clear_all_planes();
+        self.latest_frame.clone_from(&self.display);
+    }
+
     pub fn paused(&self) -> bool {
         self.paused
     }
@@ -103,16 +1356,346 @@ impl Chip {
         self.paused = paused;
     }
 
-    fn fetch_instruction(&mut self) -> u16 {
-        let left = self.memory.read(self.registers.pc()) as u16;
-        let right = self.memory.read(self.registers.pc() + 1) as u16;
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    /// Sets how many instructions run per second, clamped between 1 and
+    /// MAX_CLOCK_HZ. Returns the clock speed that was set.
+    pub fn set_clock_hz(&mut self, clock_hz: u32) -> u32 {
+        self.clock_hz = clock_hz.clamp(1, Self::MAX_CLOCK_HZ);
+        self.clock_remainder = 0;
+        if let Some(check) = self.strict_timing.as_mut() {
+            *check = TimingCheck::default();
+        }
+        self.clock_hz
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    /// Plays the emulation back slower or faster than real time without
+    /// changing the clock speed, e.g. 0.25 for slow motion, by running fewer
+    /// or more frames per call of cpu_cycle. Clamped between
+    /// MIN_SPEED_MULTIPLIER and MAX_SPEED_MULTIPLIER. Returns the multiplier
+    /// that was set.
+    pub fn set_speed_multiplier(&mut self, speed_multiplier: f32) -> f32 {
+        self.speed_multiplier = speed_multiplier.clamp(Self::MIN_SPEED_MULTIPLIER, Self::MAX_SPEED_MULTIPLIER);
+        self.speed_remainder = 0.0;
+        self.speed_multiplier
+    }
+
+    /// Runs the first frames after a ROM is loaded at a slower clock speed, so
+    /// the ROM can be watched starting up. Zero frames turns this off.
+    pub fn set_warmup(&mut self, frames: u32, clock_hz: u32) {
+        self.warmup_frames = frames;
+        self.warmup_hz = clock_hz.clamp(1, Self::MAX_CLOCK_HZ);
+        self.warmup_remaining = frames;
+    }
+
+    /// Panics if the total number of instructions run stops matching the
+    /// clock speed, by more than the one instruction the carried over
+    /// fraction can be off by. Only meant for testing the timing itself.
+    pub fn set_strict_timing(&mut self, strict_timing: bool) {
+        self.strict_timing = if strict_timing { Some(TimingCheck::default()) } else { None };
+    }
+
+    /// Counts a cycle's instructions towards strict timing, checking the
+    /// total against clock_hz / TIMER_HZ instructions per timer tick.
+    fn check_timing(&mut self, cycle_clock_hz: u32, instructions_executed: u32) {
+        let check = match self.strict_timing.as_mut() {
+            Some(check) => check,
+            None => return,
+        };
+        // A pause stops a cycle partway through, and the warmup runs at its
+        // own clock speed, so the count starts again after either.
+        if self.paused || cycle_clock_hz != self.clock_hz {
+            *check = TimingCheck::default();
+            return;
+        }
+
+        check.ticks += 1;
+        check.instructions += instructions_executed as u64;
+        let expected = check.ticks * self.clock_hz as u64 / TIMER_HZ as u64;
+        assert!(
+            check.instructions.abs_diff(expected) <= 1,
+            "{} instructions ran in {} timer ticks at {}Hz, expected {}",
+            check.instructions, check.ticks, self.clock_hz, expected
+        );
+    }
 
+    /// How many instructions to run this cycle. When the clock speed isn't a
+    /// multiple of TIMER_HZ, the leftover fraction is carried over to later
+    /// cycles, so that on average exactly clock_hz instructions run per
+    /// second.
+    fn next_instructions_per_cycle(&mut self) -> u32 {
+        let owed = self.current_clock_hz() + self.clock_remainder;
+        self.warmup_remaining = self.warmup_remaining.saturating_sub(1);
+        self.clock_remainder = owed % TIMER_HZ;
+        owed / TIMER_HZ
+    }
+
+    /// How many instructions the next cycle will run, for showing the
+    /// effective speed. Includes the warmup and the fraction carried over from
+    /// earlier cycles, so it can be one off from clock_hz / 60.
+    pub fn current_ipf(&self) -> u32 {
+        (self.current_clock_hz() + self.clock_remainder) / TIMER_HZ
+    }
+
+    fn current_clock_hz(&self) -> u32 {
+        if self.warmup_remaining > 0 {
+            self.warmup_hz
+        } else {
+            self.clock_hz
+        }
+    }
+
+    /// Reads the keys from the given function whenever an instruction checks
+    /// them, for hosts with their own input systems. It returns a mask of the
+    /// pressed keys, with key 0 being the least significant bit. Keys pressed
+    /// on the keyboard are replaced by the mask.
+    pub fn set_key_source(&mut self, key_source: Option<Box<dyn Fn() -> u16>>) {
+        self.key_source = key_source;
+    }
+
+    fn poll_key_source(&mut self) {
+        if let Some(key_source) = &self.key_source {
+            self.keyboard.set_pressed_mask(key_source());
+        }
+    }
+
+    /// Calls the given function with warnings instead of printing them.
+    pub fn set_warning_callback(&mut self, on_warning: impl FnMut(ChipWarning) + 'static) {
+        self.on_warning = Some(Box::new(on_warning));
+    }
+
+    /// Changes where RND gets its random numbers from, starting the generator
+    /// again from its seed. See RngKind.
+    pub fn set_rng(&mut self, kind: RngKind) {
+        self.rng = ChipRng::new(kind);
+        if self.frame_rng.is_some() {
+            self.frame_rng = Some(self.rng.split());
+        }
+    }
+
+    pub fn rng_kind(&self) -> &RngKind {
+        self.rng.kind()
+    }
+
+    /// Advances the generator by the same amount every frame, however many
+    /// times RND runs, by giving each frame its own generator split from the
+    /// main one. A ROM that runs RND a varying number of times a frame, e.g.
+    /// while waiting on a key, then gets the same numbers in later frames
+    /// across runs and emulators that do the same. The trade-off is that the
+    /// numbers no longer follow the seeded sequence of the RngKind, so they
+    /// won't match an emulator that doesn't sync its RNG to frames.
+    pub fn set_frame_synced_rng(&mut self, frame_synced_rng: bool) {
+        self.frame_rng = if frame_synced_rng { Some(self.rng.split()) } else { None };
+    }
+
+    pub fn frame_synced_rng(&self) -> bool {
+        self.frame_rng.is_some()
+    }
+
+    /// Calls the given function as soon as Fx15 or Fx18 sets a timer, so the
+    /// host can start the beep without waiting for the end of the cycle.
+    pub fn set_timer_callback(&mut self, on_timer_set: impl FnMut(TimerEvent) + 'static) {
+        self.on_timer_set = Some(Box::new(on_timer_set));
+    }
+
+    /// Calls the given function with the x and y of every pixel that collides
+    /// while Dxyn draws, for effects beyond setting VF, like a sound or
+    /// particles where things hit.
+    pub fn set_collision_callback(&mut self, on_collision: impl FnMut(u8, u8) + 'static) {
+        self.on_collision = Some(Box::new(on_collision));
+    }
+
+    /// Logs every read and write of memory made by an instruction, not
+    /// counting instruction fetches, for debugging ROMs that move a lot of
+    /// data around. This slows every memory access down, so it is off until
+    /// a callback is set.
+    pub fn set_memory_access_callback(&mut self, on_memory_access: impl FnMut(MemoryAccess) + 'static) {
+        self.on_memory_access = Some(Box::new(on_memory_access));
+    }
+
+    /// Turns memory access logging back off.
+    pub fn clear_memory_access_callback(&mut self) {
+        self.on_memory_access = None;
+    }
+
+    /// Reads memory for the running instruction, logging the access if
+    /// memory access logging is on.
+    fn read_data(&mut self, address: u16) -> u8 {
+        let value = self.memory.read(address);
+        self.log_memory_access(address, value, false);
+        value
+    }
+
+    /// Writes memory for the running instruction, logging the access if
+    /// memory access logging is on.
+    fn write_data(&mut self, address: u16, value: u8) {
+        self.memory.write(address, value);
+        self.log_memory_access(address, value, true);
+    }
+
+    fn log_memory_access(&mut self, address: u16, value: u8, is_write: bool) {
+        if let Some(on_memory_access) = self.on_memory_access.as_mut() {
+            // The program counter has already moved past the instruction.
+            let pc = self.registers.pc().wrapping_sub(2);
+            on_memory_access(MemoryAccess { address: address & self.memory.address_mask(), value, is_write, pc });
+        }
+    }
+
+    fn warn(&mut self, warning: ChipWarning) {
+        match self.on_warning.as_mut() {
+            Some(on_warning) => on_warning(warning),
+            None => println!("Warning: {}", warning),
+        }
+    }
+
+    fn warn_if_low_jump(&mut self, addr: u16) {
+        if self.quirks.warn_low_jumps && addr < PROGRAM_OFFSET {
+            self.warn(ChipWarning::LowJump(self.registers.pc().wrapping_sub(2), addr));
+        }
+    }
+
+    /// Replaces the font used by Fx29. See Memory::set_font.
+    pub fn set_font(&mut self, font: &[u8; FONT_LEN]) {
+        self.memory.set_font(font);
+    }
+
+    /// Replaces the large font used by Fx30. See Memory::set_large_font.
+    pub fn set_large_font(&mut self, font: &[u8; LARGE_FONT_LEN]) {
+        self.memory.set_large_font(font);
+    }
+
+    /// Sets whether I and memory addresses are 12 bits (Chip-8) or 16 bits
+    /// (XO-CHIP) wide.
+    pub fn set_address_bits(&mut self, address_bits: u8) {
+        self.memory.set_address_bits(address_bits);
+        self.set_i(self.registers.i());
+    }
+
+    /// Sets I, wrapping it to the width of memory addresses.
+    fn set_i(&mut self, value: u16) {
+        self.registers.set_i(value & self.memory.address_mask());
+    }
+
+    /// The sprite that a Dxyn instruction with the given number of rows would
+    /// draw. Rows past the end of memory are left off.
+    pub fn current_sprite(&self, rows: u8) -> Vec<u8> {
+        let start = self.registers.i() as usize;
+        let end = (start + rows as usize).min(self.memory.address_mask() as usize + 1);
+
+        (start..end).map(|address| self.memory.read(address as u16)).collect()
+    }
+
+    /// The most recently executed (pc, instruction) pairs, oldest first.
+    pub fn recent_instructions(&self) -> Vec<(u16, u16)> {
+        self.instruction_history.iter().copied().collect()
+    }
+
+    /// Sets how many executed instructions are kept for recent_instructions.
+    pub fn set_instruction_history_len(&mut self, len: usize) {
+        self.instruction_history_len = len;
+        while self.instruction_history.len() > len {
+            self.instruction_history.pop_front();
+        }
+    }
+
+    fn record_instruction(&mut self, pc: u16, instruction: u16) {
+        if self.instruction_history_len == 0 {
+            return;
+        }
+        if self.instruction_history.len() == self.instruction_history_len {
+            self.instruction_history.pop_front();
+        }
+        self.instruction_history.push_back((pc, instruction));
+    }
+
+    fn fetch_instruction(&mut self) -> u16 {
+        let instruction = self.next_instruction();
         self.registers.increment_pc();
+        instruction
+    }
 
-        left << 8 | right
+    /// The instruction at the program counter, which runs next, without
+    /// running it. An instruction at the very end of memory takes its second
+    /// byte from the start, as memory wraps around.
+    pub fn next_instruction(&self) -> u16 {
+        let pc = self.registers.pc();
+        (self.memory.read(pc) as u16) << 8 | self.memory.read(pc.wrapping_add(1)) as u16
     }
 
-    fn execute_instruction(&mut self, instruction: u16) {
+    /// The disassembly of the instruction that runs next, for a debugger.
+    pub fn next_instruction_disasm(&self) -> String {
+        disasm::disassemble(self.next_instruction())
+    }
+
+    /// In strict mode, instructions are checked for operands that are out of
+    /// range, and opcodes that aren't instructions are errors rather than
+    /// skipped, which is useful for catching malformed ROMs while developing
+    /// them. Off by default, as it slows down every instruction.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn validate_operands(&self, instruction: u16) -> Result<(), ChipError> {
+        if disasm::opcode_name(instruction) == "INVD" {
+            return Err(ChipError::UnknownInstruction(self.registers.pc().wrapping_sub(2), instruction));
+        }
+
+        let x = ((instruction & 0x0F00) >> 8) as u8;
+        let nibble = instruction & 0x000F;
+
+        let valid = match instruction & 0xF000 {
+            // 5xy0 and 9xy0 don't use their last nibble.
+            0x5000 | 0x9000 => nibble == 0,
+            // A zero height sprite only means something in high resolution.
+            0xD000 => nibble != 0 || self.display.resolution() == Resolution::High,
+            // There are only 16 keys and 16 font characters.
+            0xE000 => self.registers.v(x) <= 0xF,
+            0xF000 if instruction & 0x00FF == 0x29 => self.registers.v(x) <= 0xF,
+            // There are only two planes to select.
+            0xF000 if instruction & 0x00FF == 0x01 => x <= 3,
+            // The large font only has the digits 0 through 9.
+            0xF000 if instruction & 0x00FF == 0x30 => self.registers.v(x) <= 9,
+            _ => true,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(ChipError::InvalidOperand(self.registers.pc().wrapping_sub(2), instruction))
+        }
+    }
+
+    /// XO-CHIP's 00Dn is counted along with the Super Chip-48 instructions,
+    /// as it is the scroll up to go with 00Cn, and so is its Fn01, which
+    /// selects the planes.
+    fn is_schip_instruction(instruction: u16) -> bool {
+        matches!(instruction, 0x00C0..=0x00DF | 0x00FD..=0x00FF)
+            || instruction & 0xF0FF == 0xF030
+            || instruction & 0xF0FF == 0xF001
+    }
+
+    fn execute_instruction(&mut self, instruction: u16) -> Result<(), ChipError> {
+        if self.strict {
+            self.validate_operands(instruction)?;
+        }
+
+        if Self::is_schip_instruction(instruction) {
+            match self.quirks.schip_opcodes {
+                SchipPolicy::Enable => (),
+                SchipPolicy::Ignore => {
+                    self.debug_println_instruction("SKIP", "Super Chip-48 instructions are ignored.");
+                    return Ok(());
+                },
+                SchipPolicy::Error => return Err(ChipError::SchipInstruction(self.registers.pc().wrapping_sub(2), instruction)),
+            }
+        }
+
         let addr = instruction & 0x0FFF; // 0x0nnn
         let x = ((instruction & 0x0F00) >> 8) as u8; // 0x0x00
         let y = ((instruction & 0x00F0) >> 4) as u8; // 0x00x0
@@ -120,9 +1703,16 @@ impl Chip {
         let nibble = (instruction & 0x000F) as u8; // 0x000x
 
         match instruction & 0xF000 {
+            // The whole instruction is matched, as 00Cn, 00Dn, 00E0 and 00EE
+            // only differ in their last byte.
             0x0000 => match instruction {
+                0x00C0..=0x00CF => self.SCD_nibble(nibble),
+                0x00D0..=0x00DF => self.SCU_nibble(nibble),
                 0x00E0 => self.CLS(),
                 0x00EE => self.RET(),
+                0x00FD => self.EXIT(),
+                0x00FE => self.LOW(),
+                0x00FF => self.HIGH(),
                 _ => self.debug_println_instruction("INVD", format!("{:<#06x}", instruction))
             },
             0x1000 => self.JP_addr(addr),
@@ -158,12 +1748,14 @@ impl Chip {
                 _ => self.debug_println_instruction("INVD", format!("{:<#06x}", instruction))
             },
             0xF000 => match instruction & 0x00FF {
+                0x01 => self.PLANE_n(x),
                 0x07 => self.LD_Vx_DT(x),
                 0x0A => self.LD_Vx_K(x),
                 0x15 => self.LD_DT_Vx(x),
                 0x18 => self.LD_ST_Vx(x),
                 0x1E => self.ADD_I_Vx(x),
                 0x29 => self.LD_F_Vx(x),
+                0x30 => self.LD_HF_Vx(x),
                 0x33 => self.LD_B_Vx(x),
                 0x55 => self.LD_I_Vx(x),
                 0x65 => self.LD_Vx_I(x),
@@ -171,10 +1763,23 @@ impl Chip {
             },
             _ => self.debug_println_instruction("INVD", format!("{:<#06x}", instruction))
         }
+
+        Ok(())
     }
 
     fn debug_println_instruction(&self, instruction: impl Into<String>, description: impl Into<String>) {
-        println!("{:<#05x}: {:<16} # {}", self.registers.pc() - 2, instruction.into(), description.into());
+        if !self.log_instructions {
+            return;
+        }
+        println!("{:<#05x}: {:<16} # {}", self.registers.pc().wrapping_sub(2), instruction.into(), description.into());
+    }
+
+    /// Stores the result of an 8xyn instruction in Vx, then its flag in VF.
+    /// Both are worked out from the operands before either is written, and
+    /// the flag is written last, so with VF as Vx the flag wins.
+    fn set_v_with_flag(&mut self, x: u8, result: u8, flag: u8) {
+        self.registers.set_v(x, result);
+        self.registers.set_vf(flag);
     }
 
     // --- Instructions ---
@@ -184,6 +1789,7 @@ impl Chip {
     fn CLS(&mut self) {
         self.debug_println_instruction("CLS", "Clear the display.");
         self.display.clear();
+        self.frame_cleared = true;
     }
 
     /// 00EE - RET
@@ -198,6 +1804,62 @@ impl Chip {
         self.registers.set_pc(pc);
     }
 
+    /// 00Cn - SCD nibble
+    /// Scroll the display down n rows. (Super Chip-48)
+    ///
+    /// Every pixel moves down n rows, and the top n rows are cleared.
+    fn SCD_nibble(&mut self, nibble: u8) {
+        self.debug_println_instruction(format!("SCD  {:#03x}", nibble), "Scroll the display down n rows.");
+        self.display.scroll_down(nibble);
+        self.frame_drawn = true;
+    }
+
+    /// 00Dn - SCU nibble
+    /// Scroll the display up n rows. (XO-CHIP)
+    ///
+    /// Every pixel moves up n rows, and the bottom n rows are cleared. Both
+    /// planes are scrolled, not just the selected ones.
+    fn SCU_nibble(&mut self, nibble: u8) {
+        self.debug_println_instruction(format!("SCU  {:#03x}", nibble), "Scroll the display up n rows.");
+        self.display.scroll_up(nibble);
+        self.frame_drawn = true;
+    }
+
+    /// 00FD - EXIT
+    /// Exit the interpreter. (Super Chip-48)
+    ///
+    /// The chip is paused, as if it had halted, and a state dump written if
+    /// set_dump_on_exit has been given a file.
+    fn EXIT(&mut self) {
+        self.debug_println_instruction("EXIT", "Exit the interpreter.");
+        self.set_paused(true);
+
+        if let Some(path) = &self.dump_on_exit_path {
+            match std::fs::write(path, self.state_dump()) {
+                Ok(()) => println!("Wrote a state dump to {}", path.display()),
+                Err(err) => println!("Failed to write a state dump: {}", err),
+            }
+        }
+    }
+
+    /// 00FE - LOW
+    /// Disable high resolution graphics mode. (Super Chip-48)
+    ///
+    /// The display is switched back to 64x32 pixels and cleared.
+    fn LOW(&mut self) {
+        self.debug_println_instruction("LOW", "Disable high resolution graphics mode.");
+        self.display.set_resolution(Resolution::Low);
+    }
+
+    /// 00FF - HIGH
+    /// Enable high resolution graphics mode. (Super Chip-48)
+    ///
+    /// The display is switched to 128x64 pixels and cleared.
+    fn HIGH(&mut self) {
+        self.debug_println_instruction("HIGH", "Enable high resolution graphics mode.");
+        self.display.set_resolution(Resolution::High);
+    }
+
     /// 1nnn - JP addr
     /// Jump to location nnn.
     /// 
@@ -205,11 +1867,12 @@ impl Chip {
     fn JP_addr(&mut self, addr: u16) {
         self.debug_println_instruction(format!("JP   {:#05x}", addr), "The interpreter sets the program counter to addr.");
 
-        if addr == self.registers.pc() - 2 {
+        if addr == self.registers.pc().wrapping_sub(2) {
             self.set_paused(true);
             self.debug_println_instruction("PAUS", "The previous instruction jumped to its own address.")
         }
 
+        self.warn_if_low_jump(addr);
         self.registers.set_pc(addr);
     }
 
@@ -221,6 +1884,7 @@ impl Chip {
     fn CALL_addr(&mut self, addr: u16) {
         self.debug_println_instruction(format!("CALL {:#05x}", addr), "Call subroutine at addr.");
 
+        self.warn_if_low_jump(addr);
         self.registers.push_stack(self.registers.pc());
         self.registers.set_pc(addr);
     }
@@ -304,8 +1968,8 @@ impl Chip {
     fn OR_Vx_Vy(&mut self, x: u8, y: u8) {
         self.debug_println_instruction(format!("OR   V{:01x}, V{:01x}", x, y), "Set Vx = Vx OR Vy.");
 
-        self.registers.set_v(x, self.registers.v(x) | self.registers.v(y));
-        self.registers.set_vf(0);   // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        self.set_v_with_flag(x, self.registers.v(x) | self.registers.v(y), 0);
     }
 
     /// 8xy2 - AND Vx, Vy
@@ -318,8 +1982,8 @@ impl Chip {
     fn AND_Vx_Vy(&mut self, x: u8, y: u8) {
         self.debug_println_instruction(format!("AND  V{:01x}, V{:01x}", x, y), "Set Vx = Vx AND Vy.");
 
-        self.registers.set_v(x, self.registers.v(x) & self.registers.v(y));
-        self.registers.set_vf(0);   // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        self.set_v_with_flag(x, self.registers.v(x) & self.registers.v(y), 0);
     }
 
     /// 8xy3 - XOR Vx, Vy
@@ -332,8 +1996,8 @@ impl Chip {
     fn XOR_Vx_Vy(&mut self, x: u8, y: u8) {
         self.debug_println_instruction(format!("XOR  V{:01x}, V{:01x}", x, y), "Set Vx = Vx XOR Vy.");
 
-        self.registers.set_v(x, self.registers.v(x) ^ self.registers.v(y));
-        self.registers.set_vf(0);   // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        // According to the chip-8-test-suite: AND, OR, and XOR should set the flag register to 0.
+        self.set_v_with_flag(x, self.registers.v(x) ^ self.registers.v(y), 0);
     }
 
     /// 8xy4 - ADD Vx, Vy
@@ -347,9 +2011,7 @@ impl Chip {
 
         let sum = self.registers.v(x) as u16 + self.registers.v(y) as u16;
 
-        self.registers.set_v(x, (sum & 0x00FF) as u8);
-
-        self.registers.set_vf((sum > 255) as u8);
+        self.set_v_with_flag(x, (sum & 0x00FF) as u8, (sum > 255) as u8);
     }
 
     /// 8xy5 - SUB Vx, Vy
@@ -362,9 +2024,7 @@ impl Chip {
 
         let not_borrow = self.registers.v(x) >= self.registers.v(y);
 
-        self.registers.set_v(x, self.registers.v(x).wrapping_sub(self.registers.v(y)));
-
-        self.registers.set_vf(not_borrow as u8);
+        self.set_v_with_flag(x, self.registers.v(x).wrapping_sub(self.registers.v(y)), not_borrow as u8);
     }
 
     /// 8xy6 - SHR Vx {, Vy}
@@ -376,9 +2036,7 @@ impl Chip {
         self.debug_println_instruction(format!("SHR  V{:01x} {{,V{:01x}}}", x, y), "Set Vx = Vy SHR 1, VF = lost bit.");
 
         let v_y = self.registers.v(y);
-        self.registers.set_v(x, v_y >> 1);
-
-        self.registers.set_vf(v_y & 0b00000001);
+        self.set_v_with_flag(x, v_y >> 1, v_y & 0b00000001);
     }
 
     /// 8xy7 - SUBN Vx, Vy
@@ -391,8 +2049,7 @@ impl Chip {
 
         let not_borrow = self.registers.v(y) >= self.registers.v(x);
 
-        self.registers.set_v(x, self.registers.v(y).wrapping_sub(self.registers.v(x)));
-        self.registers.set_vf(not_borrow as u8);
+        self.set_v_with_flag(x, self.registers.v(y).wrapping_sub(self.registers.v(x)), not_borrow as u8);
     }
 
     /// 8xyE - SHL Vx {, Vy}
@@ -404,9 +2061,7 @@ impl Chip {
         self.debug_println_instruction(format!("SHL  V{:01x} {{, V{:01x}}}", x, y), "Set Vy = Vx SHL 1, VF = lost bit.");
 
         let v_y = self.registers.v(y);
-        self.registers.set_v(x, v_y << 1);
-
-        self.registers.set_vf((v_y & 0b10000000 == 0b10000000) as u8);
+        self.set_v_with_flag(x, v_y << 1, (v_y & 0b10000000 == 0b10000000) as u8);
     }
 
     /// 9xy0 - SNE Vx, Vy
@@ -429,7 +2084,7 @@ impl Chip {
     fn LD_I_addr(&mut self, addr: u16) {
         self.debug_println_instruction(format!("LD   I, {:#05x}", addr), "Set I = addr.");
 
-        self.registers.set_i(addr);
+        self.set_i(addr);
     }
 
     /// Bnnn - JP V0, addr
@@ -439,7 +2094,9 @@ impl Chip {
     fn JP_V0_addr(&mut self, addr: u16) {
         self.debug_println_instruction(format!("JP   V0, {:#05x}", addr), "Jump to the location addr + V0.");
 
-        self.registers.set_pc((self.registers.v(0) as u16).wrapping_add(addr));
+        let addr = (self.registers.v(0) as u16).wrapping_add(addr);
+        self.warn_if_low_jump(addr);
+        self.registers.set_pc(addr);
     }
 
     /// Cxkk - RND Vx, byte
@@ -451,7 +2108,10 @@ impl Chip {
     fn RND_Vx_byte(&mut self, x: u8, byte: u8) {
         self.debug_println_instruction(format!("RND  V{:01x}, {:#04x}", x, byte), "Set Vx = random byte AND byte.");
 
-        let rand_u8: u8 = rand::thread_rng().gen();
+        let rand_u8 = match self.frame_rng.as_mut() {
+            Some(frame_rng) => frame_rng.next_byte(),
+            None => self.rng.next_byte(),
+        };
 
         self.registers.set_v(x, rand_u8 & byte);
     }
@@ -469,24 +2129,74 @@ impl Chip {
     /// the screen. See instruction 8xy3 for more information on XOR, and
     /// section 2.4, Display, for more information on the Chip-8 screen and
     /// sprites.
+    ///
+    /// On Super Chip-48, Dxy0 in high resolution draws a 16x16 sprite from 32
+    /// bytes, two bytes a row.
     fn DRW_Vx_Vy_n(&mut self, x: u8, y: u8, nibble: u8) {
         if !self.first_instruction {
             self.debug_println_instruction("WAIT", "Wait for the start of the cycle to draw a sprite.");
-            self.registers.set_pc(self.registers.pc() - 2);
+            self.registers.set_pc(self.registers.pc().wrapping_sub(2));
             return;
         }
 
         self.debug_println_instruction(format!("DRW  V{:01x}, V{:01x}, {:#03x}", x, y, nibble), "Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.");
 
+        // On Super Chip-48, a height of 0 in high resolution draws a 16x16
+        // sprite, two bytes a row. XO-CHIP reads a sprite for every selected
+        // plane, one after the other.
+        let large = nibble == 0 && self.display.resolution() == Resolution::High;
+        let height = if large { 16 } else { nibble };
+        let planes = self.display.selected_planes().count_ones() as u8;
+        let len = if large { 32 } else { nibble } * planes;
+
+        let mut rows = len;
+        if self.quirks.clamp_sprite_reads {
+            let start = (self.registers.i() & self.memory.address_mask()) as usize;
+            let remaining = self.memory.address_mask() as usize + 1 - start;
+            if remaining < len as usize {
+                rows = remaining as u8;
+                self.warn(ChipWarning::SpriteClamped(self.registers.pc().wrapping_sub(2), rows));
+            }
+        }
+
         let mut sprite: Vec<u8> = Vec::new();
 
-        for i in 0..nibble {
-            sprite.push(self.memory.read(self.registers.i().wrapping_add(i as u16)));
+        for i in 0..rows {
+            let byte = self.read_data(self.registers.i().wrapping_add(i as u16));
+            sprite.push(byte);
+        }
+
+        let (v_x, v_y) = (self.registers.v(x), self.registers.v(y));
+        if self.quirks.no_coord_wrap && (v_x as usize >= self.display.width() || v_y as usize >= self.display.height()) {
+            self.registers.set_vf(0);
+            self.last_draw_info = Some(DrawInfo { x: v_x, y: v_y, height, collided_pixels: 0 });
+            return;
         }
 
-        let collision = self.display.draw_sprite(self.registers.v(x), self.registers.v(y), sprite.as_slice());
+        let on_collision = &mut self.on_collision;
+        let on_collision = |x, y| {
+            if let Some(on_collision) = on_collision.as_mut() {
+                on_collision(x, y);
+            }
+        };
+        let (collided_rows, collided_pixels) = if large {
+            // Rows cut short by clamp_sprite_reads are left blank.
+            let mut large_sprite = vec![0; 16 * planes as usize];
+            for (row, bytes) in large_sprite.iter_mut().zip(sprite.chunks(2)) {
+                *row = u16::from_be_bytes([bytes[0], bytes.get(1).copied().unwrap_or(0)]);
+            }
+            self.display.draw_large_sprite_with_collisions(v_x, v_y, &large_sprite, on_collision)
+        } else {
+            self.display.draw_sprite_with_collisions(v_x, v_y, sprite.as_slice(), on_collision)
+        };
+        self.frame_drawn = true;
+        self.last_draw_info = Some(DrawInfo { x: v_x, y: v_y, height, collided_pixels });
 
-        self.registers.set_vf(collision as u8);
+        if self.quirks.schip_collision_count && self.display.resolution() == Resolution::High {
+            self.registers.set_vf(collided_rows);
+        } else {
+            self.registers.set_vf((collided_rows > 0) as u8);
+        }
     }
 
     /// Ex9E - SKP Vx
@@ -497,6 +2207,7 @@ impl Chip {
     fn SKP_Vx(&mut self, x: u8) {
         self.debug_println_instruction(format!("SKP  V{:01x}, K", x), "Skip next instruction if key with the value of Vx is pressed.");
 
+        self.poll_key_source();
         if self.keyboard.is_pressed(self.registers.v(x)) {
             self.registers.increment_pc();
         }
@@ -510,11 +2221,23 @@ impl Chip {
     fn SKNP_Vx(&mut self, x: u8) {
         self.debug_println_instruction(format!("SKNP V{:01x}, K", x), "Skip next instruction if key with the value of Vx is not pressed.");
 
+        self.poll_key_source();
         if !self.keyboard.is_pressed(self.registers.v(x)) {
             self.registers.increment_pc();
         }
     }
 
+    /// Fn01 - PLANE n
+    /// Select the planes to draw on and clear. (XO-CHIP)
+    ///
+    /// n is a mask of the planes, 1 for the first, 2 for the second and 3 for
+    /// both. Dxyn reads a sprite for each selected plane, and 00E0 only
+    /// clears the selected planes. With 0, nothing is drawn or cleared.
+    fn PLANE_n(&mut self, n: u8) {
+        self.debug_println_instruction(format!("PLANE {:#03x}", n), "Select the planes to draw on and clear.");
+        self.display.select_planes(n);
+    }
+
     /// Fx07 - LD Vx, DT
     /// Set Vx = delay timer value.
     /// 
@@ -536,6 +2259,7 @@ impl Chip {
             self.debug_println_instruction(format!("LD   V{:01x}, K", x), "Wait for a key press, store the value of the key in Vx.");
         }
 
+        self.poll_key_source();
         match self.keyboard.just_released() {
             Some(key) => {
                 self.waiting_for_key = false;
@@ -544,7 +2268,7 @@ impl Chip {
             None => {
                 self.waiting_for_key = true;
                 // If no key is pressed, jump back to this instruction.
-                self.registers.set_pc(self.registers.pc() - 2);
+                self.registers.set_pc(self.registers.pc().wrapping_sub(2));
             }
         }
     }
@@ -557,6 +2281,9 @@ impl Chip {
         self.debug_println_instruction(format!("LD   DT, V{:01x}", x), "Set delay timer = Vx.");
 
         self.timers.set_delay(self.registers.v(x));
+        if let Some(on_timer_set) = self.on_timer_set.as_mut() {
+            on_timer_set(TimerEvent::DelaySet(self.registers.v(x)));
+        }
     }
 
     /// Fx18 - LD ST, Vx
@@ -567,6 +2294,9 @@ impl Chip {
         self.debug_println_instruction(format!("LD   ST, V{:01x}", x), "Set sound timer = Vx.");
 
         self.timers.set_sound(self.registers.v(x));
+        if let Some(on_timer_set) = self.on_timer_set.as_mut() {
+            on_timer_set(TimerEvent::SoundSet(self.registers.v(x)));
+        }
     }
 
     /// Fx1E - ADD I, Vx
@@ -575,7 +2305,7 @@ impl Chip {
     /// The values of I and Vx are added, and the results are stored in I.
     fn ADD_I_Vx(&mut self, x: u8) {
         self.debug_println_instruction(format!("ADD  I, V{:01x}", x), "Set I = I + Vx.");
-        self.registers.set_i(self.registers.i().wrapping_add(self.registers.v(x) as u16));
+        self.set_i(self.registers.i().wrapping_add(self.registers.v(x) as u16));
     }
 
     /// Fx29 - LD F, Vx
@@ -587,7 +2317,18 @@ impl Chip {
     fn LD_F_Vx(&mut self, x: u8) {
         self.debug_println_instruction(format!("LD   F, V{:01x}", x), "Set I = location of sprite for digit Vx.");
 
-        self.registers.set_i(self.registers.v(x) as u16 * 5);
+        self.set_i(self.registers.v(x) as u16 * 5);
+    }
+
+    /// Fx30 - LD HF, Vx
+    /// Set I = location of large sprite for digit Vx. (Super Chip-48)
+    ///
+    /// The value of I is set to the location for the 8x10 sprite of the
+    /// decimal digit corresponding to the value of Vx.
+    fn LD_HF_Vx(&mut self, x: u8) {
+        self.debug_println_instruction(format!("LD   HF, V{:01x}", x), "Set I = location of large sprite for digit Vx.");
+
+        self.set_i(LARGE_FONT_OFFSET.wrapping_add(self.registers.v(x) as u16 * 10));
     }
 
     /// Fx33 - LD B, Vx
@@ -603,9 +2344,9 @@ impl Chip {
         let (hundreds, value) = (value / 100, value % 100);
         let (tens, ones) = (value / 10, value % 10);
 
-        self.memory.write(self.registers.i().wrapping_add(0), hundreds);
-        self.memory.write(self.registers.i().wrapping_add(1), tens);
-        self.memory.write(self.registers.i().wrapping_add(2), ones);
+        self.write_data(self.registers.i().wrapping_add(0), hundreds);
+        self.write_data(self.registers.i().wrapping_add(1), tens);
+        self.write_data(self.registers.i().wrapping_add(2), ones);
     }
 
     /// Fx55 - LD [I], Vx
@@ -617,11 +2358,11 @@ impl Chip {
         self.debug_println_instruction(format!("LD   I, V{:01x}", x), "Store registers V0 through Vx in memory starting at location I.");
 
         for i in 0..=x {
-            self.memory.write(self.registers.i().wrapping_add(i as u16), self.registers.v(i));
+            self.write_data(self.registers.i().wrapping_add(i as u16), self.registers.v(i));
         }
         // According to the chip-8-test-suite: The i register should be set to I + x + 1.
         // TODO: Some modern emulators did not do this, so some games break with this. Make it toggle-able.
-        self.registers.set_i(self.registers.i().wrapping_add(x as u16 + 1));
+        self.set_i(self.registers.i().wrapping_add(x as u16 + 1));
     }
 
     /// Fx65 - LD Vx, [I]
@@ -633,10 +2374,1444 @@ impl Chip {
         self.debug_println_instruction(format!("LD   V{:01x}, I", x), "Read registers V0 through Vx from memory starting at location I.");
 
         for i in 0..=x {
-            self.registers.set_v(i, self.memory.read(self.registers.i().wrapping_add(i as u16)));
+            let value = self.read_data(self.registers.i().wrapping_add(i as u16));
+            self.registers.set_v(i, value);
         }
         // According to the chip-8-test-suite: The i register should be set to I + x + 1.
         // TODO: Some modern emulators did not do this, so some games break with this. Make it toggle-able.
-        self.registers.set_i(self.registers.i().wrapping_add(x as u16 + 1));
+        self.set_i(self.registers.i().wrapping_add(x as u16 + 1));
+    }
+}
+
+/// Decompresses a gzip compressed ROM. Decompressing stops one byte past the
+/// largest ROM that fits, so a small file can't unpack into gigabytes, and
+/// load_rom still sees that the ROM is too large.
+#[cfg(feature = "flate2")]
+fn gunzip<R: std::io::Read>(reader: R, max_len: usize) -> Result<Vec<u8>, GzipRomError> {
+    let mut rom_data = Vec::new();
+    let decoder = flate2::read::GzDecoder::new(reader);
+    std::io::Read::read_to_end(&mut std::io::Read::take(decoder, max_len as u64 + 1), &mut rom_data)
+        .map_err(GzipRomError::Decompress)?;
+    Ok(rom_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ADD V1, 1 then JP 0x200, forever, so every instruction is counted
+    /// without the chip ever stopping.
+    const COUNTING_LOOP: [u8; 4] = [0x71, 0x01, 0x12, 0x00];
+
+    fn chip_with(rom: &[u8]) -> Chip {
+        let mut chip = Chip::headless(Quirks::new());
+        chip.load_rom(rom).unwrap();
+        chip
+    }
+
+    #[test]
+    fn half_speed_runs_half_the_frames_in_real_time() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.set_speed_multiplier(0.5);
+        for _ in 0..60 {
+            chip.cpu_cycle().unwrap();
+        }
+
+        assert_eq!(chip.cycles(), 30);
+        assert_eq!(chip.instruction_count(), 30 * (DEFAULT_CLOCK_HZ / TIMER_HZ) as u64);
+    }
+
+    #[test]
+    fn speed_multiplier_is_clamped() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        assert_eq!(chip.set_speed_multiplier(0.0), Chip::MIN_SPEED_MULTIPLIER);
+        assert_eq!(chip.set_speed_multiplier(100.0), Chip::MAX_SPEED_MULTIPLIER);
+    }
+
+    #[test]
+    fn frame_counting_runners_ignore_the_speed_multiplier() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.set_speed_multiplier(0.5);
+
+        chip.step_frame().unwrap();
+        assert_eq!(chip.cycles(), 1);
+        assert_eq!(chip.instruction_count(), (DEFAULT_CLOCK_HZ / TIMER_HZ) as u64);
+
+        chip.run_seconds(1.0).unwrap();
+        assert_eq!(chip.cycles(), 61);
+    }
+
+    /// Waits three frames on the delay timer, then draws a 0 at x = 70, which
+    /// only shows up if the x coordinate wraps around the screen.
+    const DELAYED_WRAPPED_DRAW: [u8; 18] = [
+        0x60, 0x46, // LD V0, 70
+        0x61, 0x03, // LD V1, 3
+        0xF1, 0x15, // LD DT, V1
+        0xF2, 0x07, // LD V2, DT
+        0x32, 0x00, // SE V2, 0
+        0x12, 0x06, // JP 0x206
+        0xA0, 0x00, // LD I, 0
+        0xD0, 0x15, // DRW V0, V1, 5
+        0x12, 0x10, // JP 0x210
+    ];
+
+    fn record(rom: &[u8], frames: usize) -> Recording {
+        let mut chip = chip_with(rom);
+        chip.start_recording();
+        for _ in 0..frames {
+            chip.run_frame().unwrap();
+        }
+        chip.stop_recording().unwrap()
+    }
+
+    #[test]
+    fn replay_matches_on_the_chip_that_recorded_it() {
+        let mut chip = chip_with(&DELAYED_WRAPPED_DRAW);
+        chip.start_recording();
+        for _ in 0..10 {
+            chip.run_frame().unwrap();
+        }
+        let recording = chip.stop_recording().unwrap();
+
+        assert_eq!(chip.replay_with_verification(&recording), None);
+    }
+
+    #[test]
+    fn replay_with_an_altered_decoder_diverges_where_it_draws() {
+        let recording = record(&DELAYED_WRAPPED_DRAW, 10);
+        let blank = Display::new(10, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0)).pixels_hash();
+        let draw_frame = recording.frames.iter().position(|frame| frame.display_hash != blank).unwrap();
+        assert_eq!(draw_frame, 4);
+
+        // Without wrapping, DRW reads x = 70 as off the screen.
+        let mut altered = recording.clone();
+        altered.quirks.no_coord_wrap = true;
+        let report = Chip::headless(Quirks::new()).replay_with_verification(&altered).unwrap();
+
+        assert_eq!(report.frame, draw_frame);
+        assert_eq!(report.expected_hash, recording.frames[draw_frame].display_hash);
+        assert_eq!(report.actual_hash, blank);
+    }
+
+    #[test]
+    fn load_rom_paused_starts_from_a_clean_machine() {
+        let mut chip = chip_with(&DELAYED_WRAPPED_DRAW);
+        for _ in 0..10 {
+            chip.run_frame().unwrap();
+        }
+
+        chip.load_rom_paused(&COUNTING_LOOP).unwrap();
+        assert!(chip.paused());
+        assert_eq!(chip.registers().pc(), PROGRAM_OFFSET);
+        assert_eq!(chip.registers().v(0), 0);
+        assert_eq!(chip.display().population_count(), 0);
+
+        for _ in 0..5 {
+            chip.cpu_cycle().unwrap();
+        }
+        assert_eq!(chip.cycles(), 0);
+        assert_eq!(chip.instruction_count(), 0);
+
+        chip.set_paused(false);
+        chip.cpu_cycle().unwrap();
+        assert_eq!(chip.cycles(), 1);
+    }
+
+    #[test]
+    fn strict_timing_holds_at_a_clock_speed_with_a_remainder() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.set_clock_hz(700);
+        chip.set_strict_timing(true);
+        for _ in 0..600 {
+            chip.run_frame().unwrap();
+        }
+        assert_eq!(chip.instruction_count(), 7000);
+    }
+
+    #[test]
+    #[should_panic(expected = "instructions ran in")]
+    fn strict_timing_trips_on_a_wrong_instruction_count() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.set_strict_timing(true);
+        chip.run_frame().unwrap();
+
+        // Owes the next cycle five instructions it never earned.
+        chip.clock_remainder = 5 * TIMER_HZ;
+        chip.run_frame().unwrap();
+    }
+
+    #[test]
+    fn every_loader_rejects_a_rom_too_large_for_memory() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        let max_len = chip.memory().max_rom_len();
+        let too_large = vec![0xAA; max_len + 1];
+
+        assert_eq!(chip.load_rom(&too_large), Err(RomTooLarge { len: max_len + 1, max_len }));
+        let err = chip.load_rom_from_reader(too_large.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(Chip::run_headless(&too_large, 1).stop_reason, StopReason::RomTooLarge(_)));
+
+        // Memory is left as it was.
+        assert_eq!(chip.memory().rom_len(), COUNTING_LOOP.len());
+        assert_eq!(chip.memory().read(PROGRAM_OFFSET), COUNTING_LOOP[0]);
+    }
+
+    #[test]
+    fn a_rom_that_fills_memory_loads() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        let max_len = chip.memory().max_rom_len();
+        assert_eq!(chip.load_rom(&vec![0xAA; max_len]), Ok(()));
+        assert_eq!(chip.memory().read(0xFFF), 0xAA);
+    }
+
+    /// Runs random opcodes, each on a fresh chip with random registers, in and
+    /// out of strict mode. None of them may panic, and strict mode must turn
+    /// down exactly the opcodes that aren't instructions.
+    #[test]
+    fn random_opcodes_dont_panic() {
+        let mut rng = ChipRng::new(RngKind::Xorshift(0x5EED));
+        for _ in 0..20_000 {
+            let opcode = [rng.next_byte(), rng.next_byte()];
+            let instruction = u16::from_be_bytes(opcode);
+            let defined = disasm::opcode_name(instruction) != "INVD";
+
+            for strict in [false, true] {
+                let mut chip = chip_with(&opcode);
+                chip.set_strict(strict);
+                if rng.next_byte() & 1 == 1 {
+                    chip.set_address_bits(16);
+                }
+                for x in 0..16 {
+                    chip.edit_v(x, rng.next_byte());
+                }
+                chip.edit_i(u16::from_be_bytes([rng.next_byte(), rng.next_byte()]));
+
+                match chip.step_once() {
+                    Ok(()) => assert!(!strict || defined, "{:#06x} ran in strict mode", instruction),
+                    Err(ChipError::UnknownInstruction(pc, err_instruction)) => {
+                        assert!(strict && !defined, "{:#06x} was turned down", instruction);
+                        assert_eq!((pc, err_instruction), (PROGRAM_OFFSET, instruction));
+                    }
+                    Err(ChipError::InvalidOperand(pc, err_instruction)) => {
+                        assert!(strict && defined, "{:#06x} had an invalid operand", instruction);
+                        assert_eq!((pc, err_instruction), (PROGRAM_OFFSET, instruction));
+                    }
+                    Err(err) => panic!("{:#06x} failed with {}", instruction, err),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_rom_too_large_for_16_bit_memory_is_rejected() {
+        let mut memory = Memory::new();
+        memory.set_address_bits(16);
+        assert_eq!(memory.max_rom_len(), 0xFE00);
+        assert!(memory.load_rom(&vec![0x12; 0xFE00]).is_ok());
+        assert_eq!(memory.load_rom(&vec![0x12; 0xFE01]), Err(RomTooLarge { len: 0xFE01, max_len: 0xFE00 }));
+    }
+
+    #[test]
+    fn the_program_counter_wraps_at_the_end_of_16_bit_memory() {
+        let mut chip = chip_with(&[]);
+        chip.set_address_bits(16);
+        chip.poke(0xFFFE, 0x00);
+        chip.poke(0xFFFF, 0xE0);
+        chip.registers.set_pc(0xFFFE);
+        chip.step_once().unwrap();
+        assert_eq!(chip.registers().pc(), 0x0000);
+    }
+
+    #[cfg(feature = "flate2")]
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn a_gzip_rom_decompresses_and_loads() {
+        let mut chip = chip_with(&[]);
+        chip.load_rom_from_gzip_reader(gzip(&COUNTING_LOOP).as_slice()).unwrap();
+        assert_eq!(chip.memory().rom_len(), COUNTING_LOOP.len());
+        assert_eq!(chip.memory().read(PROGRAM_OFFSET + 1), COUNTING_LOOP[1]);
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn a_corrupt_gzip_rom_is_a_decompress_error() {
+        let mut corrupt = gzip(&COUNTING_LOOP);
+        corrupt.truncate(corrupt.len() / 2);
+        let mut chip = chip_with(&[]);
+        assert!(matches!(chip.load_rom_from_gzip_reader(corrupt.as_slice()), Err(GzipRomError::Decompress(_))));
+        assert!(matches!(chip.load_rom_from_gzip_reader(&b"not gzip"[..]), Err(GzipRomError::Decompress(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn a_gzip_rom_too_large_for_memory_is_rejected() {
+        let mut chip = chip_with(&[]);
+        let max_len = chip.memory().max_rom_len();
+        let err = chip.load_rom_from_gzip_reader(gzip(&vec![0; max_len * 4]).as_slice()).unwrap_err();
+        assert_eq!(err.to_string(), RomTooLarge { len: max_len + 1, max_len }.to_string());
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn a_gzip_rom_path_loads_with_the_uncompressed_sidecar() {
+        let dir = std::env::temp_dir().join(format!("chirp-8-gzip-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("game.ch8.gz"), gzip(&COUNTING_LOOP)).unwrap();
+        std::fs::write(dir.join("game.ch8.toml"), "clock_hz = 1200\n").unwrap();
+        std::fs::write(dir.join("corrupt.ch8.gz"), b"not gzip").unwrap();
+
+        let mut chip = chip_with(&[]);
+        chip.load_rom_from_path(&dir.join("game.ch8.gz")).unwrap();
+        assert_eq!(chip.memory().rom_len(), COUNTING_LOOP.len());
+        assert_eq!(chip.clock_hz(), 1200);
+
+        let err = chip.load_rom_from_path(&dir.join("corrupt.ch8.gz")).unwrap_err();
+        let gzip_err = err.get_ref().and_then(|err| err.downcast_ref::<GzipRomError>());
+        assert!(matches!(gzip_err, Some(GzipRomError::Decompress(_))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Runs the instructions one at a time, so every DRW draws straight away.
+    fn step(chip: &mut Chip, instructions: usize) {
+        for _ in 0..instructions {
+            chip.step_once().unwrap();
+        }
+    }
+
+    /// Switches to high resolution and draws the sprite at 0x20C twice at
+    /// (V0, V1), the second time one row lower, with the given height.
+    fn high_res_draws(height: u8, sprite: &[u8]) -> Vec<u8> {
+        let mut rom = vec![
+            0x00, 0xFF, // HIGH
+            0xA2, 0x0C, // LD I, 0x20C
+            0xD0, 0x10 | height, // DRW V0, V1, height
+            0x71, 0x01, // ADD V1, 1
+            0xD0, 0x10 | height, // DRW V0, V1, height
+            0x12, 0x0A, // JP 0x20A
+        ];
+        rom.extend_from_slice(sprite);
+        rom
+    }
+
+    #[test]
+    fn schip_collision_count_counts_the_rows_that_collided_in_high_res() {
+        let rom = high_res_draws(4, &[0xFF, 0xFF, 0xFF, 0xFF]);
+        let mut quirks = Quirks::new();
+        quirks.schip_collision_count = true;
+
+        // Moved down a row, three of the four rows land on the first sprite.
+        let mut chip = Chip::headless(quirks);
+        chip.load_rom(&rom).unwrap();
+        step(&mut chip, 5);
+        assert_eq!(chip.registers().vf(), 3);
+
+        let mut chip = chip_with(&rom);
+        step(&mut chip, 5);
+        assert_eq!(chip.registers().vf(), 1);
+    }
+
+    #[test]
+    fn dxy0_draws_a_16x16_sprite_in_high_res() {
+        let mut sprite = [0xFF; 32];
+        sprite[1] = 0x00;
+        let mut chip = chip_with(&high_res_draws(0, &sprite));
+
+        step(&mut chip, 3);
+        assert_eq!(chip.display().population_count(), 16 * 16 - 8);
+        assert!(chip.display().is_pixel_on(15, 15));
+        assert!(!chip.display().is_pixel_on(8, 0));
+        assert_eq!(chip.last_draw_info().unwrap().height, 16);
+        assert_eq!(chip.registers().vf(), 0);
+
+        step(&mut chip, 2);
+        assert_eq!(chip.registers().vf(), 1);
+    }
+
+    #[test]
+    fn strict_mode_only_accepts_dxy0_in_high_res() {
+        let mut chip = chip_with(&[0xD0, 0x10]);
+        chip.set_strict(true);
+        assert!(matches!(chip.step_once(), Err(ChipError::InvalidOperand(0x200, 0xD010))));
+
+        let mut chip = chip_with(&high_res_draws(0, &[0xFF; 32]));
+        chip.set_strict(true);
+        step(&mut chip, 3);
+        assert_eq!(chip.display().population_count(), 16 * 16);
+    }
+
+    #[test]
+    fn scroll_up_moves_both_planes_whichever_are_selected() {
+        let mut chip = chip_with(&[
+            0xF3, 0x01, // PLANE 3
+            0x61, 0x04, // LD V1, 4
+            0xA2, 0x0E, // LD I, 0x20E
+            0xD0, 0x12, // DRW V0, V1, 2
+            0xF1, 0x01, // PLANE 1
+            0x00, 0xD2, // SCU 2
+            0x12, 0x0C, // JP 0x20C
+            0x80, 0x80, 0x40, 0x40, // A column on each plane
+        ]);
+
+        step(&mut chip, 4);
+        assert_eq!(chip.display().pixel_planes(0, 4), 0b01);
+        assert_eq!(chip.display().pixel_planes(1, 5), 0b10);
+
+        step(&mut chip, 2);
+        let display = chip.display();
+        for y in 2..4 {
+            assert_eq!(display.pixel_planes(0, y), 0b01);
+            assert_eq!(display.pixel_planes(1, y), 0b10);
+        }
+        assert_eq!(display.population_count(), 4);
+    }
+
+    #[test]
+    fn plane_selects_which_planes_draws_target() {
+        let mut chip = chip_with(&[
+            0xA2, 0x0C, // LD I, 0x20C
+            0xF2, 0x01, // PLANE 2
+            0xD0, 0x11, // DRW V0, V1, 1
+            0xF3, 0x01, // PLANE 3
+            0xD0, 0x11, // DRW V0, V1, 1
+            0x12, 0x0A, // JP 0x20A
+            0xC0, 0xC0, // One row per plane
+        ]);
+        assert_eq!(chip.selected_planes(), 0b01);
+
+        step(&mut chip, 3);
+        assert_eq!(chip.selected_planes(), 0b10);
+        assert_eq!(chip.display().plane_population_counts(), [0, 2]);
+
+        // With both selected, the sprite is two bytes: a row for each plane.
+        // The second plane's row turns its first two pixels back off.
+        step(&mut chip, 2);
+        assert_eq!(chip.selected_planes(), 0b11);
+        assert_eq!(chip.display().pixel_planes(0, 0), 0b01);
+        assert_eq!(chip.display().plane_population_counts(), [2, 0]);
+        assert_eq!(chip.registers().vf(), 1);
+    }
+
+    #[test]
+    fn recent_instructions_keep_the_last_n_in_order() {
+        let mut chip = chip_with(&[
+            0x60, 0x01, // LD V0, 1
+            0x61, 0x02, // LD V1, 2
+            0x62, 0x03, // LD V2, 3
+            0x63, 0x04, // LD V3, 4
+            0x12, 0x08, // JP 0x208
+        ]);
+        chip.set_instruction_history_len(3);
+
+        step(&mut chip, 2);
+        assert_eq!(chip.recent_instructions(), [(0x200, 0x6001), (0x202, 0x6102)]);
+
+        step(&mut chip, 3);
+        assert_eq!(chip.recent_instructions(), [(0x204, 0x6203), (0x206, 0x6304), (0x208, 0x1208)]);
+
+        chip.set_instruction_history_len(1);
+        assert_eq!(chip.recent_instructions(), [(0x208, 0x1208)]);
+        chip.set_instruction_history_len(0);
+        step(&mut chip, 1);
+        assert!(chip.recent_instructions().is_empty());
+    }
+
+    /// LD I, 0xFFF then ADD I, V0 and LD [I], V0 with V0 = 1, forever.
+    const I_PAST_0XFFF: [u8; 8] = [0xAF, 0xFF, 0x60, 0x01, 0xF0, 0x1E, 0xF0, 0x55];
+
+    #[test]
+    fn i_wraps_at_0x1000_with_12_bit_addresses() {
+        let mut chip = chip_with(&I_PAST_0XFFF);
+        step(&mut chip, 3);
+        assert_eq!(chip.registers().i(), 0x000);
+
+        // The store lands in the font, as 0x1000 wraps to 0x000.
+        step(&mut chip, 1);
+        assert_eq!(chip.memory().read(0x000), 0x01);
+        assert_eq!(chip.registers().i(), 0x001);
+    }
+
+    #[test]
+    fn i_reaches_0xffff_with_16_bit_addresses() {
+        let mut chip = chip_with(&I_PAST_0XFFF);
+        chip.set_address_bits(16);
+        step(&mut chip, 4);
+        assert_eq!(chip.registers().i(), 0x1001);
+        assert_eq!(chip.memory().read(0x1000), 0x01);
+        assert_eq!(chip.memory().read(0x000), 0xF0);
+
+        chip.edit_i(0xFFFE);
+        chip.registers.set_pc(0x204);
+        step(&mut chip, 2);
+        assert_eq!(chip.memory().read(0xFFFF), 0x01);
+        assert_eq!(chip.registers().i(), 0x0000);
+
+        // Going back to 12 bits wraps I into the smaller memory.
+        chip.edit_i(0xFFFF);
+        chip.set_address_bits(12);
+        assert_eq!(chip.registers().i(), 0x0FFF);
+    }
+
+    #[test]
+    fn waiting_for_key_is_reported_until_a_key_is_released() {
+        let mut chip = chip_with(&[0xF3, 0x0A, 0x12, 0x02]); // LD V3, K
+        assert!(!chip.waiting_for_key());
+
+        step(&mut chip, 3);
+        assert!(chip.waiting_for_key());
+        assert_eq!(chip.registers().pc(), 0x200);
+
+        // Holding a key down isn't enough, Fx0A waits for it to be released.
+        chip.keyboard_mut().set_pressed(0x5, true);
+        step(&mut chip, 1);
+        assert!(chip.waiting_for_key());
+
+        chip.keyboard_mut().set_pressed(0x5, false);
+        step(&mut chip, 1);
+        assert!(!chip.waiting_for_key());
+        assert_eq!(chip.registers().v(3), 0x5);
+        assert_eq!(chip.registers().pc(), 0x202);
+    }
+
+    #[test]
+    fn current_sprite_reads_the_font_glyph_at_i() {
+        let mut chip = chip_with(&[0x60, 0x07, 0xF0, 0x29]); // LD F, 7
+        step(&mut chip, 2);
+        assert_eq!(chip.current_sprite(5), [0xF0, 0x10, 0x20, 0x40, 0x40]);
+
+        // Reads stop at the end of memory.
+        chip.edit_i(0xFFE);
+        assert_eq!(chip.current_sprite(5).len(), 2);
+    }
+
+    #[test]
+    fn batch_run_matches_running_each_rom_in_turn() {
+        let roms: Vec<(&[u8], usize)> = vec![
+            (include_bytes!("../../roms/tests/1-chip8-logo.ch8"), 60),
+            (include_bytes!("../../roms/tests/2-ibm-logo.ch8"), 60),
+            (include_bytes!("../../roms/tests/3-corax+.ch8"), 120),
+            (include_bytes!("../../roms/tests/4-flags.ch8"), 120),
+            (include_bytes!("../../roms/tests/2-ibm-logo.ch8"), 1),
+        ];
+
+        let batch = Chip::batch_run(roms.clone());
+        assert_eq!(batch.len(), roms.len());
+        for (result, (rom_data, cycles)) in batch.iter().zip(roms) {
+            assert_eq!(result.hash, Chip::run_and_hash(rom_data, cycles));
+            assert_eq!(result.cycles, Chip::run_headless(rom_data, cycles).cycles);
+        }
+        assert_ne!(batch[0].hash, batch[1].hash);
+        assert_ne!(batch[1].hash, batch[4].hash);
+    }
+
+    #[test]
+    fn clock_speed_is_clamped_to_max_clock_hz() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        assert_eq!(chip.set_clock_hz(u32::MAX), Chip::MAX_CLOCK_HZ);
+        assert_eq!(chip.clock_hz(), Chip::MAX_CLOCK_HZ);
+        assert_eq!(chip.set_clock_hz(0), 1);
+        assert_eq!(chip.set_clock_hz(1000), 1000);
+    }
+
+    #[test]
+    fn one_emulated_second_runs_clock_hz_instructions_and_60_ticks() {
+        let mut chip = chip_with(&[
+            0x60, 0x3C, // LD V0, 60
+            0xF0, 0x15, // LD DT, V0
+            0x71, 0x01, // ADD V1, 1
+            0x12, 0x04, // JP 0x204
+        ]);
+        chip.set_clock_hz(700);
+
+        chip.run_seconds(0.5).unwrap();
+        assert_eq!(chip.cycles(), 30);
+        assert_eq!(chip.timers.delay(), 30);
+
+        chip.run_seconds(0.5).unwrap();
+        assert_eq!(chip.instruction_count(), 700);
+        assert_eq!(chip.cycles(), 60);
+        assert_eq!(chip.timers.delay(), 0);
+    }
+
+    #[test]
+    fn export_disassembly_lists_the_rom() {
+        let rom = include_bytes!("../../roms/tests/2-ibm-logo.ch8");
+        let chip = chip_with(rom);
+        let path = std::env::temp_dir().join(format!("chirp-8-disasm-test-{}.txt", std::process::id()));
+        chip.export_disassembly(&path).unwrap();
+        let listing = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(
+            lines[..4],
+            ["0x200: 00 e0  CLS", "0x202: a2 2a  LD   I, 0x22a", "0x204: 60 0c  LD   V0, 0x0c", "0x206: 61 08  LD   V1, 0x08"]
+        );
+        assert_eq!(lines.len(), rom.len() / 2);
+    }
+
+    #[test]
+    fn nothing_runs_until_the_startup_delay_has_passed() {
+        let mut chip = Chip::headless(Quirks::new());
+        chip.set_startup_delay(Duration::from_secs(1) / TIMER_HZ * 6);
+        chip.load_rom(&COUNTING_LOOP).unwrap();
+
+        for _ in 0..6 {
+            chip.run_frame().unwrap();
+            assert_eq!(chip.instruction_count(), 0);
+        }
+        chip.run_frame().unwrap();
+        assert_eq!(chip.instruction_count(), 10);
+
+        // Loading another ROM waits again.
+        chip.load_rom(&COUNTING_LOOP).unwrap();
+        chip.run_frame().unwrap();
+        assert_eq!(chip.instruction_count(), 10);
+    }
+
+    #[test]
+    fn clear_display_blanks_the_screen_and_leaves_the_registers() {
+        let mut chip = chip_with(&[
+            0xF3, 0x01, // PLANE 3
+            0x65, 0x2A, // LD V5, 0x2A
+            0xA2, 0x0C, // LD I, 0x20C
+            0xD0, 0x11, // DRW V0, V1, 1
+            0xF1, 0x01, // PLANE 1
+            0x12, 0x0A, // JP 0x20A
+            0xFF, 0xFF, // A row on each plane
+        ]);
+        step(&mut chip, 5);
+        assert_eq!(chip.display().plane_population_counts(), [8, 8]);
+        chip.display.mark_clean();
+        let registers = (chip.registers().pc(), chip.registers().i(), chip.registers().v(5));
+
+        chip.clear_display();
+        assert_eq!(chip.display().population_count(), 0);
+        assert_eq!(chip.latest_frame().population_count(), 0);
+        assert!(chip.display().is_dirty());
+        assert_eq!((chip.registers().pc(), chip.registers().i(), chip.registers().v(5)), registers);
+        assert_eq!(chip.selected_planes(), 0b01);
+    }
+
+    #[test]
+    fn a_sprite_at_x_70_wraps_unless_no_coord_wrap_is_set() {
+        let rom = [
+            0x60, 0x46, // LD V0, 70
+            0xA0, 0x00, // LD I, 0x000 (the font's 0)
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x12, 0x06, // JP 0x206
+        ];
+
+        let mut chip = chip_with(&rom);
+        step(&mut chip, 3);
+        assert!(chip.display().is_pixel_on(6, 0) && chip.display().is_pixel_on(9, 4));
+        assert_eq!(chip.display().population_count(), 14);
+
+        let mut quirks = Quirks::new();
+        quirks.no_coord_wrap = true;
+        let mut chip = Chip::headless(quirks);
+        chip.load_rom(&rom).unwrap();
+        step(&mut chip, 3);
+        assert_eq!(chip.display().population_count(), 0);
+        assert_eq!(chip.last_draw_info().unwrap().x, 70);
+    }
+
+    #[test]
+    fn latest_frame_only_changes_at_the_end_of_a_cycle() {
+        let mut chip = chip_with(&[
+            0xA0, 0x00, // LD I, 0x000
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x12, 0x04, // JP 0x204
+        ]);
+
+        // Drawn partway through a cycle, the sprite isn't presented yet.
+        step(&mut chip, 2);
+        assert_eq!(chip.display().population_count(), 14);
+        assert_eq!(chip.latest_frame().population_count(), 0);
+
+        chip.run_frame().unwrap();
+        assert_eq!(chip.latest_frame().pixels_hash(), chip.display().pixels_hash());
+
+        // A sprite drawn and cleared within one cycle is never presented.
+        let mut chip = chip_with(&[
+            0xA0, 0x00, // LD I, 0x000
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x00, 0xE0, // CLS
+            0x12, 0x06, // JP 0x206
+        ]);
+        chip.run_frame().unwrap();
+        chip.run_frame().unwrap();
+        assert!(chip.last_draw_info().is_some());
+        assert_eq!(chip.latest_frame().population_count(), 0);
+    }
+
+    #[cfg(feature = "zip")]
+    fn zip_of(files: &[(&str, &[u8])]) -> std::io::Cursor<Vec<u8>> {
+        use std::io::Write;
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        std::io::Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn a_zip_with_one_rom_loads_it() {
+        let archive = zip_of(&[("readme.txt", b"hello"), ("Pong.CH8", &COUNTING_LOOP)]);
+        let mut chip = Chip::headless(Quirks::new());
+        chip.load_rom_from_zip_reader(archive, None).unwrap();
+        assert_eq!((0..4).map(|offset| chip.memory().read(0x200 + offset)).collect::<Vec<_>>(), COUNTING_LOOP);
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn a_zip_with_several_roms_needs_an_entry_name() {
+        let files: [(&str, &[u8]); 2] = [("a.ch8", &[0x00, 0xE0]), ("b.ch8", &COUNTING_LOOP)];
+        let mut chip = Chip::headless(Quirks::new());
+        assert!(matches!(chip.load_rom_from_zip_reader(zip_of(&files), None), Err(ZipRomError::MultipleRoms(2))));
+        assert!(matches!(
+            chip.load_rom_from_zip_reader(zip_of(&files), Some("c.ch8")),
+            Err(ZipRomError::MissingEntry(name)) if name == "c.ch8"
+        ));
+
+        chip.load_rom_from_zip_reader(zip_of(&files), Some("b.ch8")).unwrap();
+        assert_eq!(chip.memory().rom_len(), COUNTING_LOOP.len());
+        assert_eq!(chip.memory().read(0x200), 0x71);
+
+        let empty = zip_of(&[("readme.txt", b"hello")]);
+        assert!(matches!(chip.load_rom_from_zip_reader(empty, None), Err(ZipRomError::NoRoms)));
+    }
+
+    /// Runs JP 0x100 once, returning the warnings sent and where the chip
+    /// ended up.
+    fn low_jump_warnings(warn_low_jumps: bool) -> (Vec<ChipWarning>, u16) {
+        let quirks = Quirks { warn_low_jumps, ..Quirks::new() };
+        let mut chip = Chip::headless(quirks);
+        chip.load_rom(&[0x11, 0x00]).unwrap();
+        let warnings: std::rc::Rc<std::cell::RefCell<Vec<ChipWarning>>> = Default::default();
+        let sent = warnings.clone();
+        chip.set_warning_callback(move |warning| std::cell::RefCell::borrow_mut(&sent).push(warning));
+        chip.step_once().unwrap();
+        let warnings = std::cell::RefCell::borrow(&warnings).clone();
+        (warnings, chip.registers().pc())
+    }
+
+    #[test]
+    fn a_jump_below_the_program_warns_only_when_asked_to() {
+        assert_eq!(low_jump_warnings(true), (vec![ChipWarning::LowJump(0x200, 0x100)], 0x100));
+        assert_eq!(low_jump_warnings(false), (vec![], 0x100));
+    }
+
+    #[test]
+    fn a_sidecar_overrides_the_default_settings() {
+        let dir = std::env::temp_dir().join(format!("chirp-8-sidecar-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("game.ch8"), COUNTING_LOOP).unwrap();
+        std::fs::write(
+            dir.join("game.ch8.toml"),
+            "clock_hz = 1000\npalette = \"amber\"\n\n[quirks]\nschip_collision_count = true\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("plain.ch8"), COUNTING_LOOP).unwrap();
+
+        let mut chip = Chip::headless(Quirks::new());
+        chip.load_rom_from_path(&dir.join("plain.ch8")).unwrap();
+        assert_eq!((chip.clock_hz(), chip.rom_palette(), chip.quirks()), (600, None, Quirks::new()));
+
+        chip.load_rom_from_path(&dir.join("game.ch8")).unwrap();
+        assert_eq!(chip.clock_hz(), 1000);
+        assert_eq!(chip.rom_palette(), Some(Palette::AMBER));
+        assert_eq!(chip.quirks(), Quirks { schip_collision_count: true, ..Quirks::new() });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn step_frame_runs_a_frame_of_instructions_and_one_timer_tick() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.timers.set_delay(10);
+        chip.set_paused(true);
+
+        chip.step_frame().unwrap();
+        // 600Hz is ten instructions a frame, half of them ADDs.
+        assert_eq!(chip.registers().v(1), 5);
+        assert_eq!(chip.timers.delay(), 9);
+        assert!(chip.paused());
+
+        chip.step_frame().unwrap();
+        assert_eq!(chip.registers().v(1), 10);
+        assert_eq!(chip.timers.delay(), 8);
+    }
+
+    /// The SuspectedHang events sent over each of the given number of frames.
+    fn hang_events(rom: &[u8], frames: usize) -> Vec<Vec<u32>> {
+        let mut chip = chip_with(rom);
+        chip.set_hang_threshold(Some(3));
+        (0..frames)
+            .map(|_| {
+                chip.run_frame().unwrap();
+                std::iter::from_fn(|| chip.poll_event())
+                    .filter_map(|event| match event {
+                        ChipEvent::SuspectedHang(frames) => Some(frames),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_clear_and_jump_spin_is_a_suspected_hang_after_the_threshold() {
+        // CLS then JP 0x200.
+        assert_eq!(hang_events(&[0x00, 0xE0, 0x12, 0x00], 4), [vec![], vec![], vec![3], vec![]]);
+        // Drawing between the clears is a ROM animating, not a hang.
+        assert_eq!(hang_events(&[0x00, 0xE0, 0xD0, 0x15, 0x12, 0x00], 4), vec![Vec::<u32>::new(); 4]);
+    }
+
+    #[test]
+    fn fx29_points_at_a_custom_font() {
+        let mut font = [0; FONT_LEN];
+        for (i, byte) in font.iter_mut().enumerate() {
+            *byte = i as u8 ^ 0xA5;
+        }
+        assert_eq!((0..FONT_LEN as u16).map(|i| Memory::with_font(&font).read(i)).collect::<Vec<_>>(), font);
+
+        // LD V0, 7 then LD F, V0.
+        let mut chip = chip_with(&[0x60, 0x07, 0xF0, 0x29]);
+        chip.set_font(&font);
+        step(&mut chip, 2);
+        assert_eq!(chip.registers().i(), 35);
+        assert_eq!((35..40).map(|i| chip.memory().read(i)).collect::<Vec<_>>(), font[35..40]);
+
+        // The large font goes straight after it, leaving it alone.
+        chip.set_large_font(&[0xFF; LARGE_FONT_LEN]);
+        assert_eq!(chip.memory().read(FONT_LEN as u16 - 1), font[FONT_LEN - 1]);
+        assert_eq!(chip.memory().read(LARGE_FONT_OFFSET), 0xFF);
+    }
+
+    #[test]
+    fn fx30_points_at_the_large_digit() {
+        let quirks = Quirks { schip_opcodes: SchipPolicy::Enable, ..Quirks::new() };
+        let mut chip = Chip::headless(quirks);
+        // LD V0, 5 then LD HF, V0.
+        chip.load_rom(&[0x60, 0x05, 0xF0, 0x30]).unwrap();
+        step(&mut chip, 2);
+        assert_eq!(chip.registers().i(), 0x82);
+        let five: Vec<_> = (0x82..0x8C).map(|i| chip.memory().read(i)).collect();
+        assert_eq!(five, [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF]);
+
+        // There is no large A.
+        let mut chip = Chip::headless(quirks);
+        chip.load_rom(&[0x60, 0x0A, 0xF0, 0x30]).unwrap();
+        chip.set_strict(true);
+        step(&mut chip, 1);
+        assert!(matches!(chip.step_once(), Err(ChipError::InvalidOperand(0x202, 0xF030))));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn state_json_has_the_registers_after_running() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.timers.set_delay(3);
+        step(&mut chip, 3);
+
+        let state: serde_json::Value = serde_json::from_str(&chip.state_json()).unwrap();
+        assert_eq!(state["registers"]["v"][1], 2);
+        assert_eq!(state["registers"]["i"], 0);
+        assert_eq!(state["registers"]["pc"], 0x202);
+        assert_eq!(state["registers"]["stack"], serde_json::json!([]));
+        assert_eq!(state["timers"]["delay"], 3);
+        assert_eq!(state["display"]["width"], 64);
+        assert_eq!(state["display"]["rows"][0], ".".repeat(64));
+    }
+
+    #[test]
+    fn a_panic_while_running_writes_a_crash_report() {
+        let path = std::env::temp_dir().join(format!("chirp-8-crash-test-{}.txt", std::process::id()));
+        // LD V0, 1 then JP 0x100, which panics in the warning callback.
+        let mut chip = Chip::headless(Quirks { warn_low_jumps: true, ..Quirks::new() });
+        chip.load_rom(&[0x60, 0x01, 0x11, 0x00]).unwrap();
+        chip.set_warning_callback(|warning| panic!("deliberate: {}", warning));
+        chip.install_crash_handler(&path);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| chip.run_frame()));
+        assert!(result.is_err());
+
+        let report = std::fs::read_to_string(&path).unwrap();
+        assert!(report.contains("Panic: deliberate: instruction at 0x202 jumped below the program"));
+        assert!(report.contains(&format!("PC: {:#05x}", chip.registers().pc())));
+        assert!(report.contains("  0x200: 6001  LD   V0, 0x01"));
+        assert!(report.contains("  0x202: 1100  JP   0x100"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn emulated_time_counts_frames_but_not_pauses() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        for _ in 0..90 {
+            chip.run_frame().unwrap();
+        }
+        assert_eq!(chip.emulated_time(), Duration::from_millis(1500));
+
+        chip.set_paused(true);
+        for _ in 0..30 {
+            chip.run_frame().unwrap();
+        }
+        assert_eq!(chip.emulated_time(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn the_display_sink_gets_each_finished_frame() {
+        type Lit = std::rc::Rc<std::cell::RefCell<Vec<(usize, usize)>>>;
+        let lit: Lit = Default::default();
+        let written = lit.clone();
+        let sink = crate::emulator::PixelWriter::new(move |x, y, on| {
+            if on {
+                std::cell::RefCell::borrow_mut(&written).push((x, y));
+            }
+        });
+
+        // DRW V0, V0, 5 with I at the font's 0, then JP to itself.
+        let mut chip = chip_with(&[0xD0, 0x05, 0x12, 0x02]);
+        chip.set_display_sink(Box::new(sink));
+        chip.run_frame().unwrap();
+
+        let mut zero = vec![];
+        for (y, row) in [0b1111, 0b1001, 0b1001, 0b1001, 0b1111].iter().enumerate() {
+            zero.extend((0..4).filter(|x| row & (0b1000 >> x) != 0).map(|x| (x, y)));
+        }
+        assert_eq!(*std::cell::RefCell::borrow(&lit), zero);
+
+        chip.run_frame().unwrap();
+        assert_eq!(std::cell::RefCell::borrow(&lit).len(), zero.len() * 2);
+    }
+
+    #[test]
+    fn the_warmup_frames_run_at_the_warmup_clock() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.set_warmup(3, 120);
+        let mut per_frame = vec![];
+        for _ in 0..5 {
+            let before = chip.instruction_count();
+            chip.run_frame().unwrap();
+            per_frame.push(chip.instruction_count() - before);
+        }
+        assert_eq!(per_frame, [2, 2, 2, 10, 10]);
+
+        // Loading a ROM warms up again.
+        chip.load_rom(&COUNTING_LOOP).unwrap();
+        assert_eq!(chip.current_ipf(), 2);
+    }
+
+    /// The delay timer after stepping 20 instructions from 10 while paused,
+    /// which is two timer ticks' worth at 600Hz.
+    fn delay_after_paused_steps(freeze_timers_when_paused: Option<bool>) -> u8 {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.timers.set_delay(10);
+        chip.set_paused(true);
+        if let Some(freeze_timers_when_paused) = freeze_timers_when_paused {
+            chip.set_freeze_timers_when_paused(freeze_timers_when_paused);
+        }
+        step(&mut chip, 20);
+        chip.timers.delay()
+    }
+
+    #[test]
+    fn paused_steps_only_tick_the_timers_when_not_frozen() {
+        assert_eq!(delay_after_paused_steps(None), 10);
+        assert_eq!(delay_after_paused_steps(Some(true)), 10);
+        assert_eq!(delay_after_paused_steps(Some(false)), 8);
+    }
+
+    /// The FrameHash events from running the IBM logo for 30 frames.
+    fn ibm_logo_frame_hashes() -> Vec<(u64, u64)> {
+        let mut chip = chip_with(include_bytes!("../../roms/tests/2-ibm-logo.ch8"));
+        chip.set_frame_hash_logging(true);
+        for _ in 0..30 {
+            chip.run_frame().unwrap();
+        }
+        std::iter::from_fn(|| chip.poll_event())
+            .filter_map(|event| match event {
+                ChipEvent::FrameHash(frame, hash) => Some((frame, hash)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_hashes_are_the_same_every_run() {
+        let hashes = ibm_logo_frame_hashes();
+        assert_eq!(hashes, ibm_logo_frame_hashes());
+        assert_eq!(hashes.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(), (1..=30).collect::<Vec<_>>());
+
+        // The logo is drawn a sprite a frame, then stays put.
+        assert_ne!(hashes[0].1, hashes[1].1);
+        assert_eq!(hashes[28].1, hashes[29].1);
+    }
+
+    /// Sets VF and V1, runs the instruction and returns V1 and VF.
+    fn after_8xy(instruction: u16, vf: u8, v1: u8) -> (u8, u8) {
+        let [high, low] = instruction.to_be_bytes();
+        let mut chip = chip_with(&[0x6F, vf, 0x61, v1, high, low]);
+        step(&mut chip, 3);
+        (chip.registers().v(1), chip.registers().vf())
+    }
+
+    #[test]
+    fn the_flag_wins_when_an_8xy_instruction_writes_to_vf() {
+        // (instruction, VF, V1, VF after), with VF as Vx and V1 as Vy.
+        let cases = [
+            (0x8F11, 0x0F, 0xF0, 0),
+            (0x8F12, 0xFF, 0x0F, 0),
+            (0x8F13, 0x0F, 0xF0, 0),
+            (0x8F14, 0xFF, 0x02, 1),
+            (0x8F14, 0x01, 0x02, 0),
+            (0x8F15, 0x05, 0x03, 1),
+            (0x8F15, 0x03, 0x05, 0),
+            (0x8F16, 0x00, 0x03, 1),
+            (0x8F16, 0xFF, 0x02, 0),
+            (0x8F17, 0x03, 0x05, 1),
+            (0x8F17, 0x05, 0x03, 0),
+            (0x8F1E, 0x00, 0x80, 1),
+            (0x8F1E, 0xFF, 0x40, 0),
+        ];
+        for (instruction, vf, v1, flag) in cases {
+            assert_eq!(after_8xy(instruction, vf, v1), (v1, flag), "{:04X} with VF={:#04x} V1={:#04x}", instruction, vf, v1);
+        }
+
+        // With VF as Vy, the result comes from VF before the flag replaces it.
+        assert_eq!(after_8xy(0x81F4, 0x01, 0xFF), (0x00, 1));
+        assert_eq!(after_8xy(0x81F5, 0x03, 0x05), (0x02, 1));
+        assert_eq!(after_8xy(0x81F7, 0x05, 0x03), (0x02, 1));
+        assert_eq!(after_8xy(0x81F6, 0x05, 0x00), (0x02, 1));
+        assert_eq!(after_8xy(0x81FE, 0x81, 0x00), (0x02, 1));
+    }
+
+    #[test]
+    fn a_rom_that_never_ends_stops_at_the_cycle_cap() {
+        let too_large = vec![0; 0x1000];
+        let roms: Vec<(&[u8], usize)> = vec![(&COUNTING_LOOP, 50), (&[0x12, 0x00], 50), (&too_large, 50)];
+        let results = Chip::batch_run(roms);
+
+        assert!(matches!(results[0].stop_reason, StopReason::CycleCapReached));
+        assert_eq!(results[0].cycles, 50);
+        assert!(matches!(results[1].stop_reason, StopReason::Halted));
+        assert_eq!(results[1].cycles, 1);
+        assert!(matches!(results[2].stop_reason, StopReason::RomTooLarge(_)));
+        assert_eq!(results[2].cycles, 0);
+    }
+
+    #[test]
+    fn the_histogram_counts_each_mnemonic_once_turned_on() {
+        // LD V0, 3, then ADD V1, 1 and SE V0, V1 until V1 is 3, then JP to itself.
+        let rom = [0x60, 0x03, 0x71, 0x01, 0x50, 0x10, 0x12, 0x02, 0x12, 0x08];
+        let mut chip = chip_with(&rom);
+        step(&mut chip, 2);
+        assert!(chip.opcode_histogram().is_empty());
+
+        chip.set_opcode_histogram(true);
+        // SE, JP, ADD, SE, JP, ADD, SE, then the JP to itself.
+        step(&mut chip, 8);
+        let expected: HashMap<&str, u64> = [("ADD", 2), ("SE", 3), ("JP", 3)].iter().copied().collect();
+        assert_eq!(chip.opcode_histogram(), expected);
+
+        chip.set_opcode_histogram(false);
+        step(&mut chip, 1);
+        assert!(chip.opcode_histogram().is_empty());
+    }
+
+    /// Runs 00FF under the policy, returning the result, the resolution
+    /// afterwards and where the chip ended up.
+    fn high_under(schip_opcodes: SchipPolicy) -> (Result<(), ChipError>, Resolution, u16) {
+        let mut chip = Chip::headless(Quirks { schip_opcodes, ..Quirks::new() });
+        chip.load_rom(&[0x00, 0xFF]).unwrap();
+        let result = chip.step_once();
+        (result, chip.display().resolution(), chip.registers().pc())
+    }
+
+    #[test]
+    fn each_schip_policy_handles_00ff() {
+        assert_eq!(Quirks::new().schip_opcodes, SchipPolicy::Enable);
+        assert!(matches!(high_under(SchipPolicy::Enable), (Ok(()), Resolution::High, 0x202)));
+        assert!(matches!(high_under(SchipPolicy::Ignore), (Ok(()), Resolution::Low, 0x202)));
+        assert!(matches!(
+            high_under(SchipPolicy::Error),
+            (Err(ChipError::SchipInstruction(0x200, 0x00FF)), Resolution::Low, _)
+        ));
+    }
+
+    #[test]
+    fn last_draw_info_counts_the_collided_pixels() {
+        // Draws the font's 0 at (5, 3), then its 1 over the top.
+        let rom = [0x60, 0x05, 0x61, 0x03, 0xD0, 0x15, 0xA0, 0x05, 0xD0, 0x15];
+        let mut chip = chip_with(&rom);
+        step(&mut chip, 2);
+        assert_eq!(chip.last_draw_info(), None);
+
+        step(&mut chip, 1);
+        assert_eq!(chip.last_draw_info(), Some(DrawInfo { x: 5, y: 3, height: 5, collided_pixels: 0 }));
+
+        // 0xF0 & 0x20 in the top row and 0xF0 & 0x70 in the bottom one.
+        step(&mut chip, 2);
+        assert_eq!(chip.last_draw_info(), Some(DrawInfo { x: 5, y: 3, height: 5, collided_pixels: 4 }));
+        assert_eq!(chip.registers().vf(), 1);
+    }
+
+    #[test]
+    fn exit_writes_a_state_dump_when_asked_to() {
+        let path = std::env::temp_dir().join(format!("chirp-8-exit-dump-test-{}.txt", std::process::id()));
+        // LD VA, 0x42 then EXIT.
+        let rom = [0x6A, 0x42, 0x00, 0xFD];
+
+        let mut chip = chip_with(&rom);
+        step(&mut chip, 2);
+        assert!(!path.exists());
+
+        let mut chip = chip_with(&rom);
+        chip.set_dump_on_exit(Some(path.clone()));
+        step(&mut chip, 2);
+        let dump = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(dump, chip.state_dump());
+        assert!(dump.starts_with("CHIRP-8 state dump\n"));
+        assert!(dump.contains("Va: 0x42"));
+        assert!(dump.contains("\n0x0200: 6a 42 00 fd 00"));
+        assert_eq!(dump.lines().filter(|line| line.starts_with("0x")).count(), 0x1000 / 16);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Draws a 15 row sprite from 0xFFD, with the last three bytes of memory
+    /// all lit, returning the lit rows of the first column and the warnings.
+    fn tall_sprite_at_the_end_of_memory(clamp_sprite_reads: bool) -> (Vec<usize>, Vec<ChipWarning>) {
+        let mut chip = Chip::headless(Quirks { clamp_sprite_reads, ..Quirks::new() });
+        // LD I, 0xFFD then DRW V0, V0, 15.
+        chip.load_rom(&[0xAF, 0xFD, 0xD0, 0x0F]).unwrap();
+        for address in 0xFFD..=0xFFF {
+            chip.memory_mut().write(address, 0xFF);
+        }
+        let warnings: std::rc::Rc<std::cell::RefCell<Vec<ChipWarning>>> = Default::default();
+        let sent = warnings.clone();
+        chip.set_warning_callback(move |warning| std::cell::RefCell::borrow_mut(&sent).push(warning));
+        step(&mut chip, 2);
+
+        let lit_rows = (0..15).filter(|&y| chip.display().is_pixel_on(0, y)).collect();
+        let warnings = std::cell::RefCell::borrow(&warnings).clone();
+        (lit_rows, warnings)
+    }
+
+    #[test]
+    fn a_sprite_past_the_end_of_memory_only_draws_the_rows_in_memory_when_clamped() {
+        assert_eq!(tall_sprite_at_the_end_of_memory(true), (vec![0, 1, 2], vec![ChipWarning::SpriteClamped(0x202, 3)]));
+
+        // Otherwise it wraps around into the font, starting with the 0.
+        let (lit_rows, warnings) = tall_sprite_at_the_end_of_memory(false);
+        assert_eq!(lit_rows[..4], [0, 1, 2, 3]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn current_ipf_is_the_next_frames_instruction_budget() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        assert_eq!(chip.current_ipf(), 10);
+
+        // 1000Hz is 16 and two thirds instructions a frame.
+        chip.set_clock_hz(1000);
+        let mut budgets = vec![];
+        for _ in 0..3 {
+            let before = chip.instruction_count();
+            let ipf = chip.current_ipf();
+            chip.run_frame().unwrap();
+            assert_eq!(chip.instruction_count() - before, ipf as u64);
+            budgets.push(ipf);
+        }
+        assert_eq!(budgets, [16, 17, 17]);
+    }
+
+    #[test]
+    fn a_key_source_answers_the_key_checks() {
+        // LD V0, 3, then SKP V0 over LD V1, 1, then LD V2, 1 and SKNP V0
+        // over LD V3, 1.
+        let rom = [0x60, 0x03, 0xE0, 0x9E, 0x61, 0x01, 0x62, 0x01, 0xE0, 0xA1, 0x63, 0x01];
+        let v1_to_v3 = |key_mask: u16| {
+            let mut chip = chip_with(&rom);
+            // The source's mask replaces what the keyboard has pressed.
+            chip.keyboard_mut().set_pressed(3, true);
+            chip.set_key_source(Some(Box::new(move || key_mask)));
+            while chip.registers().pc() < 0x200 + rom.len() as u16 {
+                chip.step_once().unwrap();
+            }
+            (chip.registers().v(1), chip.registers().v(2), chip.registers().v(3))
+        };
+
+        assert_eq!(v1_to_v3(1 << 3), (0, 1, 1));
+        assert_eq!(v1_to_v3(0), (1, 1, 0));
+        assert_eq!(v1_to_v3(0xFFFF), (0, 1, 1));
+    }
+
+    #[test]
+    fn scroll_down_and_clear_are_told_apart() {
+        assert_eq!(disasm::disassemble(0x00C4), "SCD  0x4");
+        assert_eq!(disasm::disassemble(0x00E0), "CLS");
+        assert_eq!(disasm::disassemble(0x00CE), "SCD  0xe");
+
+        // DRW V0, V0, 1 with the font's 0, then SCD 4, then CLS.
+        let mut chip = chip_with(&[0xD0, 0x01, 0x00, 0xC4, 0x00, 0xE0]);
+        step(&mut chip, 2);
+        let lit_rows: Vec<_> = (0..chip.display().height()).filter(|&y| chip.display().is_pixel_on(0, y)).collect();
+        assert_eq!(lit_rows, [4]);
+        step(&mut chip, 1);
+        assert_eq!(chip.display().pixels(), [0; 64]);
+    }
+
+    #[test]
+    fn the_live_loop_pauses_at_a_breakpoint() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.add_breakpoint(0x202);
+
+        chip.cpu_cycle().unwrap();
+        assert!(chip.paused());
+        assert_eq!((chip.registers().pc(), chip.registers().v(1)), (0x202, 1));
+        assert!(matches!(chip.poll_event(), Some(ChipEvent::BreakpointHit(0x202))));
+
+        // Paused, the cycles carry on without running anything.
+        chip.cpu_cycle().unwrap();
+        assert_eq!(chip.registers().v(1), 1);
+
+        // Resuming runs the instruction at the breakpoint, stopping there again
+        // after one more time around the loop.
+        chip.set_paused(false);
+        chip.cpu_cycle().unwrap();
+        assert!(chip.paused());
+        assert_eq!((chip.registers().pc(), chip.registers().v(1)), (0x202, 2));
+        assert!(matches!(chip.poll_event(), Some(ChipEvent::BreakpointHit(0x202))));
+    }
+
+    #[test]
+    fn setting_a_timer_is_reported_straight_away() {
+        let events: std::rc::Rc<std::cell::RefCell<Vec<TimerEvent>>> = Default::default();
+        // LD V0, 30; LD ST, V0; LD DT, V0.
+        let mut chip = chip_with(&[0x60, 0x1E, 0xF0, 0x18, 0xF0, 0x15]);
+        let recorded = events.clone();
+        chip.set_timer_callback(move |event| std::cell::RefCell::borrow_mut(&recorded).push(event));
+
+        step(&mut chip, 2);
+        assert_eq!(*events.borrow(), [TimerEvent::SoundSet(30)]);
+        step(&mut chip, 1);
+        assert_eq!(*events.borrow(), [TimerEvent::SoundSet(30), TimerEvent::DelaySet(30)]);
+    }
+
+    #[test]
+    fn the_stack_lists_nested_return_addresses_in_order() {
+        // CALL 0x204; then at 0x204, CALL 0x208; then at 0x208, JP 0x208.
+        let mut chip = chip_with(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x12, 0x08]);
+        step(&mut chip, 1);
+        assert_eq!(chip.registers().stack(), [0x202]);
+        step(&mut chip, 1);
+        assert_eq!(chip.registers().stack(), [0x202, 0x206]);
+        assert_eq!(chip.registers().pc(), 0x208);
+    }
+
+    #[test]
+    fn undoing_edits_puts_back_what_they_replaced() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.poke(0x200, 0xAB);
+        chip.edit_v(3, 0x42);
+        assert_eq!((chip.memory().read(0x200), chip.registers().v(3)), (0xAB, 0x42));
+
+        assert!(chip.undo_edit());
+        assert_eq!(chip.registers().v(3), 0);
+        assert!(chip.undo_edit());
+        assert_eq!(chip.memory().read(0x200), 0x71);
+        assert!(!chip.undo_edit());
+    }
+
+    #[test]
+    fn step_over_runs_a_whole_subroutine() {
+        // CALL 0x206; LD V2, 1; JP 0x204; then at 0x206, LD V1, 5; ADD V1, 1; RET.
+        let mut chip = chip_with(&[0x22, 0x06, 0x62, 0x01, 0x12, 0x04, 0x61, 0x05, 0x71, 0x01, 0x00, 0xEE]);
+        chip.step_over().unwrap();
+        assert_eq!(chip.registers().pc(), 0x202);
+        assert_eq!(chip.registers().v(1), 6);
+        assert!(chip.registers().stack().is_empty());
+        assert_eq!(chip.instruction_count(), 4);
+
+        // Anything else is a single step.
+        chip.step_over().unwrap();
+        assert_eq!((chip.registers().pc(), chip.registers().v(2)), (0x204, 1));
+        assert_eq!(chip.instruction_count(), 5);
+
+        // A breakpoint inside the subroutine stops it partway.
+        let mut chip = chip_with(&[0x22, 0x06, 0x62, 0x01, 0x12, 0x04, 0x61, 0x05, 0x71, 0x01, 0x00, 0xEE]);
+        chip.add_breakpoint(0x208);
+        chip.step_over().unwrap();
+        assert!(chip.paused());
+        assert_eq!((chip.registers().pc(), chip.registers().v(1)), (0x208, 5));
+    }
+
+    #[test]
+    fn frame_advanced_is_sent_once_per_frame() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        chip.run_frame().unwrap();
+        assert!(chip.poll_event().is_none());
+
+        chip.set_frame_events(true);
+        chip.run_seconds(1.0).unwrap();
+        let mut frames = Vec::new();
+        while let Some(event) = chip.poll_event() {
+            if let ChipEvent::FrameAdvanced { cycle, frame } = event {
+                frames.push((cycle, frame));
+            }
+        }
+
+        let instructions_per_frame = (DEFAULT_CLOCK_HZ / TIMER_HZ) as u64;
+        let expected: Vec<_> = (2..=61).map(|frame| (frame * instructions_per_frame, frame)).collect();
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn bcd_logs_three_writes() {
+        let accesses: std::rc::Rc<std::cell::RefCell<Vec<MemoryAccess>>> = Default::default();
+        // LD V0, 234; LD I, 0x300; LD B, V0.
+        let mut chip = chip_with(&[0x60, 0xEA, 0xA3, 0x00, 0xF0, 0x33]);
+        let recorded = accesses.clone();
+        chip.set_memory_access_callback(move |access| std::cell::RefCell::borrow_mut(&recorded).push(access));
+        step(&mut chip, 3);
+
+        let write = |address, value| MemoryAccess { address, value, is_write: true, pc: 0x204 };
+        assert_eq!(*accesses.borrow(), [write(0x300, 2), write(0x301, 3), write(0x302, 4)]);
+
+        chip.clear_memory_access_callback();
+        chip.registers.set_pc(0x204);
+        step(&mut chip, 1);
+        assert_eq!(accesses.borrow().len(), 3);
+    }
+
+    /// The next bytes the chip's main generator would give, without advancing
+    /// it.
+    fn upcoming_random_bytes(chip: &Chip) -> Vec<u8> {
+        let mut rng = chip.rng.clone();
+        (0..8).map(|_| rng.next_byte()).collect()
+    }
+
+    #[test]
+    fn the_frame_synced_rng_advances_the_same_however_often_rnd_runs() {
+        let rnd_loop = [0xC0, 0xFF, 0x12, 0x00];
+        let rng_after_frames = |rom: &[u8], frame_synced_rng: bool| {
+            let mut chip = chip_with(rom);
+            chip.set_rng(RngKind::Xorshift(7));
+            chip.set_frame_synced_rng(frame_synced_rng);
+            for _ in 0..3 {
+                chip.run_frame().unwrap();
+            }
+            upcoming_random_bytes(&chip)
+        };
+
+        assert_eq!(rng_after_frames(&rnd_loop, true), rng_after_frames(&COUNTING_LOOP, true));
+        assert_ne!(rng_after_frames(&rnd_loop, false), rng_after_frames(&COUNTING_LOOP, false));
+    }
+
+    #[test]
+    fn quirks_round_trip_and_apply_from_the_next_instruction() {
+        let quirks = Quirks { no_coord_wrap: true, schip_opcodes: SchipPolicy::Ignore, ..Quirks::new() };
+        let saved = toml::to_string(&quirks).unwrap();
+        assert_eq!(toml::from_str::<Quirks>(&saved).unwrap(), quirks);
+
+        // LD V0, 70; DRW V0, V1, 1; CLS; DRW V0, V1, 1, with I at the font's 0.
+        let mut chip = chip_with(&[0x60, 70, 0xD0, 0x11, 0x00, 0xE0, 0xD0, 0x11]);
+        step(&mut chip, 2);
+        assert!(chip.display().is_pixel_on(6, 0));
+
+        step(&mut chip, 1);
+        chip.set_quirks(quirks);
+        assert_eq!(chip.quirks(), quirks);
+        step(&mut chip, 1);
+        assert_eq!(chip.display().population_count(), 0);
+    }
+
+    #[test]
+    fn the_next_instruction_is_disassembled_without_running_it() {
+        let mut chip = chip_with(&COUNTING_LOOP);
+        assert_eq!(chip.next_instruction_disasm(), disasm::disassemble(0x7101));
+        assert_eq!((chip.registers().pc(), chip.instruction_count()), (0x200, 0));
+
+        step(&mut chip, 1);
+        assert_eq!(chip.next_instruction_disasm(), disasm::disassemble(0x1200));
+
+        // At the end of memory, the second byte comes from the start.
+        chip.memory_mut().write(0xFFF, 0x12);
+        chip.registers.set_pc(0xFFF);
+        assert_eq!(chip.next_instruction(), 0x1200 | chip.memory().read(0) as u16);
+        assert_eq!(chip.next_instruction_disasm(), disasm::disassemble(chip.next_instruction()));
+    }
+
+    #[test]
+    fn every_collided_pixel_is_passed_to_the_collision_callback() {
+        let collisions: std::rc::Rc<std::cell::RefCell<Vec<(u8, u8)>>> = Default::default();
+        // LD V0, 10; DRW V0, V1, 1; ADD V0, 1; DRW V0, V1, 1, with I at the
+        // font's 0, whose top row is 0xF0.
+        let mut chip = chip_with(&[0x60, 10, 0xD0, 0x11, 0x70, 0x01, 0xD0, 0x11]);
+        let recorded = collisions.clone();
+        chip.set_collision_callback(move |x, y| std::cell::RefCell::borrow_mut(&recorded).push((x, y)));
+
+        step(&mut chip, 2);
+        assert!(collisions.borrow().is_empty());
+        step(&mut chip, 2);
+        // The second draw overlaps the first at 11, 12 and 13.
+        assert_eq!(*collisions.borrow(), [(11, 0), (12, 0), (13, 0)]);
+        assert_eq!(chip.registers().vf(), 1);
+    }
+
+    #[test]
+    fn dumped_frames_are_one_png_per_frame() {
+        let dir = std::env::temp_dir().join(format!("chirp-8-frame-dump-test-{}", std::process::id()));
+        let mut chip = chip_with(include_bytes!("../../roms/tests/2-ibm-logo.ch8"));
+        let dumped = chip.dump_frames_to(&dir, 3);
+
+        let mut names: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        let last_frame = png::Decoder::new(std::fs::File::open(dir.join("frame-00002.png")).unwrap());
+        let mut reader = last_frame.read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        dumped.unwrap();
+        assert_eq!(names, ["frame-00000.png", "frame-00001.png", "frame-00002.png"]);
+        assert_eq!((info.width, info.height), (64, 32));
+        assert_eq!(rgba, chip.latest_frame().to_rgba(&Palette::WHITE));
+        assert_eq!(chip.cycles(), 3);
+    }
+
+    #[test]
+    fn a_save_state_only_loads_into_the_rom_it_was_saved_from() {
+        let ibm_logo = include_bytes!("../../roms/tests/2-ibm-logo.ch8");
+        let mut chip = chip_with(ibm_logo);
+        chip.run_frame().unwrap();
+        let state = chip.save_state();
+        assert_eq!(state.rom_sha1, SaveState::hash_rom(ibm_logo));
+
+        let saved_pc = chip.registers().pc();
+        let saved_pixels = chip.display().pixels();
+        step(&mut chip, 10);
+        assert_ne!(chip.registers().pc(), saved_pc);
+        chip.load_state(&state).unwrap();
+        assert_eq!(chip.registers().pc(), saved_pc);
+        assert_eq!(chip.display().pixels(), saved_pixels);
+
+        let mut other_rom = chip_with(&COUNTING_LOOP);
+        assert!(matches!(other_rom.load_state(&state), Err(StateError::RomMismatch)));
+        assert_eq!(other_rom.registers().pc(), 0x200);
+
+        let mut tampered = state.clone();
+        tampered.rom_sha1[0] ^= 1;
+        assert!(matches!(chip.load_state(&tampered), Err(StateError::RomMismatch)));
     }
 }
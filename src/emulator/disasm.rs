@@ -0,0 +1,287 @@
+use crate::emulator::Memory;
+
+/// An instruction read out of memory, along with its assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    pub address: u16,
+    pub instruction: u16,
+    pub assembly: String,
+}
+
+/// Converts an instruction into the assembly used by Cowgod's Chip-8
+/// Technical Reference.
+pub fn disassemble(instruction: u16) -> String {
+    let addr = instruction & 0x0FFF; // 0x0nnn
+    let x = ((instruction & 0x0F00) >> 8) as u8; // 0x0x00
+    let y = ((instruction & 0x00F0) >> 4) as u8; // 0x00x0
+    let byte = (instruction & 0x00FF) as u8; // 0x00xx
+    let nibble = (instruction & 0x000F) as u8; // 0x000x
+
+    match instruction & 0xF000 {
+        0x0000 => match instruction {
+            0x00C0..=0x00CF => format!("SCD  {:#03x}", nibble),
+            0x00D0..=0x00DF => format!("SCU  {:#03x}", nibble),
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            0x00FD => "EXIT".to_string(),
+            0x00FE => "LOW".to_string(),
+            0x00FF => "HIGH".to_string(),
+            _ => invalid(instruction),
+        },
+        0x1000 => format!("JP   {:#05x}", addr),
+        0x2000 => format!("CALL {:#05x}", addr),
+        0x3000 => format!("SE   V{:01x}, {:#04x}", x, byte),
+        0x4000 => format!("SNE  V{:01x}, {:#04x}", x, byte),
+        0x5000 => format!("SE   V{:01x}, V{:01x}", x, y),
+        0x6000 => format!("LD   V{:01x}, {:#04x}", x, byte),
+        0x7000 => format!("ADD  V{:01x}, {:#04x}", x, byte),
+        0x8000 => match nibble {
+            0x0 => format!("LD   V{:01x}, V{:01x}", x, y),
+            0x1 => format!("OR   V{:01x}, V{:01x}", x, y),
+            0x2 => format!("AND  V{:01x}, V{:01x}", x, y),
+            0x3 => format!("XOR  V{:01x}, V{:01x}", x, y),
+            0x4 => format!("ADD  V{:01x}, V{:01x}", x, y),
+            0x5 => format!("SUB  V{:01x}, V{:01x}", x, y),
+            0x6 => format!("SHR  V{:01x} {{, V{:01x}}}", x, y),
+            0x7 => format!("SUBN V{:01x}, V{:01x}", x, y),
+            0xE => format!("SHL  V{:01x} {{, V{:01x}}}", x, y),
+            _ => invalid(instruction),
+        },
+        0x9000 => match nibble {
+            0x0 => format!("SNE  V{:01x}, V{:01x}", x, y),
+            _ => invalid(instruction),
+        },
+        0xA000 => format!("LD   I, {:#05x}", addr),
+        0xB000 => format!("JP   V0, {:#05x}", addr),
+        0xC000 => format!("RND  V{:01x}, {:#04x}", x, byte),
+        0xD000 => format!("DRW  V{:01x}, V{:01x}, {:#03x}", x, y, nibble),
+        0xE000 => match byte {
+            0x9E => format!("SKP  V{:01x}", x),
+            0xA1 => format!("SKNP V{:01x}", x),
+            _ => invalid(instruction),
+        },
+        0xF000 => match byte {
+            0x01 => format!("PLANE {:#03x}", x),
+            0x07 => format!("LD   V{:01x}, DT", x),
+            0x0A => format!("LD   V{:01x}, K", x),
+            0x15 => format!("LD   DT, V{:01x}", x),
+            0x18 => format!("LD   ST, V{:01x}", x),
+            0x1E => format!("ADD  I, V{:01x}", x),
+            0x29 => format!("LD   F, V{:01x}", x),
+            0x30 => format!("LD   HF, V{:01x}", x),
+            0x33 => format!("LD   B, V{:01x}", x),
+            0x55 => format!("LD   [I], V{:01x}", x),
+            0x65 => format!("LD   V{:01x}, [I]", x),
+            _ => invalid(instruction),
+        },
+        _ => invalid(instruction),
+    }
+}
+
+/// Just the mnemonic of an instruction, without its operands, for grouping
+/// or coloring instructions by kind.
+pub fn opcode_name(instruction: u16) -> &'static str {
+    let byte = instruction & 0x00FF;
+    let nibble = instruction & 0x000F;
+
+    match instruction & 0xF000 {
+        0x0000 => match instruction {
+            0x00C0..=0x00CF => "SCD",
+            0x00D0..=0x00DF => "SCU",
+            0x00E0 => "CLS",
+            0x00EE => "RET",
+            0x00FD => "EXIT",
+            0x00FE => "LOW",
+            0x00FF => "HIGH",
+            _ => "INVD",
+        },
+        0x1000 | 0xB000 => "JP",
+        0x2000 => "CALL",
+        0x3000 => "SE",
+        0x4000 => "SNE",
+        0x5000 => "SE",
+        0x6000 => "LD",
+        0x7000 => "ADD",
+        0x8000 => match nibble {
+            0x0 => "LD",
+            0x1 => "OR",
+            0x2 => "AND",
+            0x3 => "XOR",
+            0x4 => "ADD",
+            0x5 => "SUB",
+            0x6 => "SHR",
+            0x7 => "SUBN",
+            0xE => "SHL",
+            _ => "INVD",
+        },
+        0x9000 if nibble == 0 => "SNE",
+        0xA000 => "LD",
+        0xC000 => "RND",
+        0xD000 => "DRW",
+        0xE000 => match byte {
+            0x9E => "SKP",
+            0xA1 => "SKNP",
+            _ => "INVD",
+        },
+        0xF000 => match byte {
+            0x01 => "PLANE",
+            0x07 | 0x0A | 0x15 | 0x18 | 0x29 | 0x30 | 0x33 | 0x55 | 0x65 => "LD",
+            0x1E => "ADD",
+            _ => "INVD",
+        },
+        _ => "INVD",
+    }
+}
+
+fn invalid(instruction: u16) -> String {
+    format!("INVD {:#06x}", instruction)
+}
+
+/// Disassembles the instructions from start up to, but not including, end.
+pub fn disassemble_range(memory: &Memory, start: u16, end: u16) -> Vec<DisassembledInstruction> {
+    (start..end)
+        .step_by(2)
+        .map(|address| {
+            let instruction =
+                (memory.read(address) as u16) << 8 | memory.read(address.wrapping_add(1)) as u16;
+            DisassembledInstruction {
+                address,
+                instruction,
+                assembly: disassemble(instruction),
+            }
+        })
+        .collect()
+}
+
+/// Where the program counter falls in a disassembled range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcPosition {
+    /// The index of the instruction at the program counter.
+    At(usize),
+    /// The program counter is in the range, but lands between two of the
+    /// disassembled instructions, so it is reading them as its own bytes.
+    Misaligned,
+    OutOfRange,
+}
+
+/// Disassembles like disassemble_range, and finds which instruction the
+/// program counter is at so a debugger can highlight it.
+pub fn disassemble_range_marked(memory: &Memory, start: u16, end: u16, pc: u16) -> (Vec<DisassembledInstruction>, PcPosition) {
+    let instructions = disassemble_range(memory, start, end);
+
+    let position = if pc < start || pc >= end {
+        PcPosition::OutOfRange
+    } else if (pc - start) & 1 != 0 {
+        PcPosition::Misaligned
+    } else {
+        PcPosition::At(((pc - start) / 2) as usize)
+    };
+
+    (instructions, position)
+}
+
+/// What can be worked out about a ROM without running it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    /// The keys checked by Ex9E and ExA1, sorted. Only keys loaded into a
+    /// register with 6xkk shortly before being checked can be found.
+    pub used_keys: Vec<u8>,
+    /// Whether the ROM waits for any key with Fx0A.
+    pub waits_for_key: bool,
+}
+
+/// Scans a ROM's instructions for the keys it uses, so a front-end can show
+/// which keys a game needs.
+pub fn inspect_rom(rom: &[u8]) -> RomInfo {
+    let mut used_keys = [false; 16];
+    let mut waits_for_key = false;
+    // The values of registers last set with 6xkk, where they are known.
+    let mut values: [Option<u8>; 16] = [None; 16];
+
+    for pair in rom.chunks_exact(2) {
+        let instruction = (pair[0] as u16) << 8 | pair[1] as u16;
+        let x = ((instruction & 0x0F00) >> 8) as usize;
+        let byte = (instruction & 0x00FF) as u8;
+
+        match instruction & 0xF000 {
+            0x6000 => values[x] = Some(byte),
+            0x7000 | 0x8000 | 0xC000 => values[x] = None,
+            0xE000 if byte == 0x9E || byte == 0xA1 => {
+                if let Some(key) = values[x] {
+                    used_keys[(key & 0xF) as usize] = true;
+                }
+            },
+            0xF000 => match byte {
+                0x0A => {
+                    waits_for_key = true;
+                    values[x] = None;
+                },
+                0x07 => values[x] = None,
+                // Fx65 loads V0 through Vx from memory.
+                0x65 => values.iter_mut().take(x + 1).for_each(|value| *value = None),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    RomInfo {
+        used_keys: (0..16).filter(|key| used_keys[*key as usize]).collect(),
+        waits_for_key,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_with(rom: &[u8]) -> Memory {
+        let mut memory = Memory::new();
+        memory.load_rom(rom).unwrap();
+        memory
+    }
+
+    #[test]
+    fn the_marked_instruction_is_the_one_at_the_pc() {
+        let memory = memory_with(&[0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x12, 0x06]);
+
+        let (instructions, position) = disassemble_range_marked(&memory, 0x200, 0x208, 0x204);
+        assert_eq!(position, PcPosition::At(2));
+        assert_eq!(instructions[2].address, 0x204);
+        assert_eq!(instructions[2].assembly, "LD   V0, 0x0c");
+
+        assert_eq!(disassemble_range_marked(&memory, 0x200, 0x208, 0x203).1, PcPosition::Misaligned);
+        // Starting on an odd address, the PC is misaligned with the listing.
+        assert_eq!(disassemble_range_marked(&memory, 0x201, 0x209, 0x204).1, PcPosition::Misaligned);
+        assert_eq!(disassemble_range_marked(&memory, 0x200, 0x208, 0x208).1, PcPosition::OutOfRange);
+        assert_eq!(disassemble_range_marked(&memory, 0x202, 0x208, 0x200).1, PcPosition::OutOfRange);
+    }
+
+    #[test]
+    fn opcodes_are_named_without_their_operands() {
+        let names: Vec<_> = [0x00E0, 0x00EE, 0x1234, 0xB200, 0x2ABC, 0x6A05, 0x8124, 0x812E, 0xA22A, 0xD015, 0xE19E, 0xF033]
+            .iter()
+            .map(|&opcode| opcode_name(opcode))
+            .collect();
+        assert_eq!(names, ["CLS", "RET", "JP", "JP", "CALL", "LD", "ADD", "SHL", "LD", "DRW", "SKP", "LD"]);
+
+        assert_eq!(opcode_name(0x8128), "INVD");
+        assert_eq!(opcode_name(0x9121), "INVD");
+    }
+
+    #[test]
+    fn the_keys_a_rom_checks_are_reported() {
+        // Checks 2, 4, 6 and 8 like arrow keys, with 8 loaded twice and V1
+        // clobbered by ADD before its key is checked again.
+        let rom = [
+            0x60, 0x02, 0xE0, 0x9E, 0x60, 0x08, 0xE0, 0xA1, 0x61, 0x04, 0xE1, 0x9E, 0x62, 0x06, 0xE2, 0xA1,
+            0x71, 0x01, 0xE1, 0x9E, 0x63, 0x08, 0xE3, 0x9E,
+        ];
+        assert_eq!(inspect_rom(&rom), RomInfo { used_keys: vec![2, 4, 6, 8], waits_for_key: false });
+
+        // LD V0, 5 then LD V0, K, so the key in V0 is no longer known.
+        let info = inspect_rom(&[0x60, 0x05, 0xF0, 0x0A, 0xE0, 0x9E]);
+        assert_eq!(info, RomInfo { used_keys: vec![], waits_for_key: true });
+    }
+}
@@ -1,5 +1,74 @@
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+use crate::emulator::Palette;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScreenshotError {
+    #[error("failed to write the screenshot file")]
+    Io(#[from] std::io::Error),
+    #[error("failed to encode the screenshot as a png")]
+    Encoding(#[from] png::EncodingError),
+}
+
+/// The logo fits in a square this many pixels wide.
+pub const LOGO_SIZE: usize = 16;
+
+/// The number of entries in grid_pixels.
+pub const GRID_PIXELS_LEN: usize = HIGH_RES_WIDTH * HIGH_RES_HEIGHT / 32;
+
+/// XO-CHIP draws on two bitplanes, which together pick one of four colors.
+pub const PLANES: usize = 2;
+
+const LOW_RES_WIDTH: usize = 64;
+const LOW_RES_HEIGHT: usize = 32;
+const HIGH_RES_WIDTH: usize = 128;
+const HIGH_RES_HEIGHT: usize = 64;
+
+/// The original Chip-8 display is 64x32 pixels. Super Chip-48 added a high
+/// resolution mode of 128x64 pixels, toggled with the 00FE and 00FF
+/// instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    Low,
+    High,
+}
+
+/// Patterns drawn by the self-test to check colors and scaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    Solid,
+    Checkerboard,
+    /// An ordered dither that gets denser from left to right.
+    Gradient,
+}
+
+impl TestPattern {
+    pub fn next(self) -> Option<TestPattern> {
+        match self {
+            TestPattern::Solid => Some(TestPattern::Checkerboard),
+            TestPattern::Checkerboard => Some(TestPattern::Gradient),
+            TestPattern::Gradient => None,
+        }
+    }
+}
+
+/// Which end of the packed columns from pixels_with_order holds the top row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    /// The most significant bit is the top row. This is what the shader
+    /// expects.
+    MsbFirst,
+    /// The least significant bit is the top row.
+    LsbFirst,
+}
+
+const BAYER_MATRIX: [[usize; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
 
 /// The original implementation of the Chip-8 language used a 64x32-pixel
 /// monochrome display with this format:
@@ -13,8 +82,19 @@ const HEIGHT: usize = 32;
 /// Chip-8 draws graphics on screen through the use of sprites. A sprite is a
 /// group of bytes which are a binary representation of the desired picture.
 /// Chip-8 sprites may be up to 15 bytes, for a possible sprite size of 8x15.
+#[derive(Clone)]
 pub struct Display {
-    pixels: [u32; HEIGHT * WIDTH / 32],
+    /// One column per entry for each XO-CHIP plane, with the most
+    /// significant bit being the top row. Low resolution mode only uses the
+    /// top-left 64x32 pixels.
+    planes: [[u64; HIGH_RES_WIDTH]; PLANES],
+    /// A mask of the planes that drawing and clearing act on, with the first
+    /// plane as the least significant bit, set by XO-CHIP's Fn01.
+    selected_planes: u8,
+    resolution: Resolution,
+    /// Whether any pixels have changed since the display was last marked
+    /// clean.
+    dirty: bool,
     scale: u32,
     background_color: (f32, f32, f32),
     foreground_color: (f32, f32, f32),
@@ -27,69 +107,439 @@ impl Display {
         foreground_color: (f32, f32, f32),
     ) -> Self {
         Self {
-            pixels: [0; HEIGHT * WIDTH / 32],
+            planes: [[0; HIGH_RES_WIDTH]; PLANES],
+            selected_planes: 1,
+            resolution: Resolution::Low,
+            dirty: true,
             scale,
             background_color,
             foreground_color,
         }
     }
 
+    /// A blank display with "C8" written in its top-left LOGO_SIZE pixels
+    /// with the font, used for the window icon.
+    pub fn logo() -> Self {
+        let memory = crate::emulator::Memory::new();
+        let glyph = |character: u16| -> Vec<u8> { (0..5).map(|row| memory.read(character * 5 + row)).collect() };
+
+        let mut display = Self::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+        display.draw_sprite(3, 5, &glyph(0xC));
+        display.draw_sprite(9, 5, &glyph(0x8));
+        display
+    }
+
+    /// Turns off every pixel on the selected planes, leaving the others as
+    /// they are, like XO-CHIP's 00E0. 00E0 runs this every frame in a lot of
+    /// ROMs, so it fills the columns at once rather than going pixel by
+    /// pixel.
     pub fn clear(&mut self) {
-        self.pixels = [0; HEIGHT * WIDTH / 32];
+        for plane in self.selected_plane_indices() {
+            self.planes[plane].fill(0);
+        }
+        self.dirty = true;
+    }
+
+    /// Turns off every pixel on every plane, whichever are selected.
+    pub fn clear_all_planes(&mut self) {
+        for plane in self.planes.iter_mut() {
+            plane.fill(0);
+        }
+        self.dirty = true;
+    }
+
+    /// Moves every pixel down by the given number of rows, in the current
+    /// resolution. Rows scrolled off the bottom are lost, and the rows at the
+    /// top are turned off. Both planes are scrolled, whichever are selected.
+    pub fn scroll_down(&mut self, rows: u8) {
+        // Low resolution only uses the top 32 rows of each column.
+        let visible = u64::MAX << (64 - self.height());
+        for column in self.planes.iter_mut().flatten() {
+            *column = column.checked_shr(rows as u32).unwrap_or(0) & visible;
+        }
+        self.dirty = true;
+    }
+
+    /// Moves every pixel up by the given number of rows, in the current
+    /// resolution. Rows scrolled off the top are lost, and the rows at the
+    /// bottom are turned off. Both planes are scrolled, whichever are
+    /// selected.
+    pub fn scroll_up(&mut self, rows: u8) {
+        let visible = u64::MAX << (64 - self.height());
+        for column in self.planes.iter_mut().flatten() {
+            *column = column.checked_shl(rows as u32).unwrap_or(0) & visible;
+        }
+        self.dirty = true;
+    }
+
+    /// The mask of planes that drawing and clearing act on, 1 by default.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    /// Selects the planes that drawing and clearing act on, from a mask with
+    /// the first plane as the least significant bit. 0 selects none, so
+    /// nothing is drawn or cleared.
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected_planes = mask & ((1 << PLANES) - 1);
+    }
+
+    /// The indices of the selected planes, first plane first.
+    fn selected_plane_indices(&self) -> impl Iterator<Item = usize> {
+        let selected_planes = self.selected_planes;
+        (0..PLANES).filter(move |plane| selected_planes & (1 << plane) != 0)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switches the display's resolution, clearing every plane in the
+    /// process.
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+        self.clear_all_planes();
+    }
+
+    pub fn width(&self) -> usize {
+        match self.resolution {
+            Resolution::Low => LOW_RES_WIDTH,
+            Resolution::High => HIGH_RES_WIDTH,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self.resolution {
+            Resolution::Low => LOW_RES_HEIGHT,
+            Resolution::High => HIGH_RES_HEIGHT,
+        }
     }
 
     /// Draws a list of bytes onto the screen. Each byte being one row.
-    /// Returns true if drawing collides with already drawn pixel.
-    pub fn draw_sprite(&mut self, mut x: u8, mut y: u8, sprite: &[u8]) -> bool {
-        let mut collided = false;
+    /// Returns the number of rows that collided with already drawn pixels.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
+        self.draw_sprite_counted(x, y, sprite).0
+    }
+
+    /// Like draw_sprite, also returning the number of pixels that collided.
+    pub fn draw_sprite_counted(&mut self, x: u8, y: u8, sprite: &[u8]) -> (u8, u32) {
+        self.draw_sprite_with_collisions(x, y, sprite, |_, _| ())
+    }
+
+    /// Like draw_sprite_counted, also calling on_collision with the
+    /// coordinates of every pixel that collided, wrapped onto the screen.
+    ///
+    /// The sprite is drawn on each selected plane. With more than one
+    /// selected, it holds each plane's rows in turn, first plane first, the
+    /// way XO-CHIP's Dxyn reads them, and the collisions on every plane are
+    /// added up.
+    pub fn draw_sprite_with_collisions(
+        &mut self,
+        x: u8,
+        y: u8,
+        sprite: &[u8],
+        mut on_collision: impl FnMut(u8, u8),
+    ) -> (u8, u32) {
+        let rows: Vec<u16> = sprite.iter().map(|byte| *byte as u16).collect();
+        self.draw_on_selected_planes(x, y, &rows, 8, &mut on_collision)
+    }
+
+    /// Draws a Super Chip-48 16x16 sprite, DXY0 in high resolution, with
+    /// each row as two bytes, left then right. Planes and collisions work
+    /// like draw_sprite_with_collisions, with 16 rows for each plane.
+    pub fn draw_large_sprite_with_collisions(
+        &mut self,
+        x: u8,
+        y: u8,
+        sprite: &[u16],
+        mut on_collision: impl FnMut(u8, u8),
+    ) -> (u8, u32) {
+        self.draw_on_selected_planes(x, y, sprite, 16, &mut on_collision)
+    }
+
+    /// Splits a sprite's rows evenly between the selected planes and draws
+    /// each share on its plane.
+    fn draw_on_selected_planes(
+        &mut self,
+        x: u8,
+        y: u8,
+        rows: &[u16],
+        bits: u32,
+        on_collision: &mut impl FnMut(u8, u8),
+    ) -> (u8, u32) {
+        let planes: Vec<usize> = self.selected_plane_indices().collect();
+        if planes.is_empty() || rows.is_empty() {
+            return (0, 0);
+        }
+
+        let rows_per_plane = (rows.len() / planes.len()).max(1);
+        let mut collided_rows = 0;
+        let mut collided_pixels = 0;
+        for (plane, plane_rows) in planes.into_iter().zip(rows.chunks(rows_per_plane)) {
+            let (rows, pixels) = self.draw_rows(plane, x, y, plane_rows, bits, on_collision);
+            collided_rows += rows;
+            collided_pixels += pixels;
+        }
+        (collided_rows, collided_pixels)
+    }
+
+    /// Draws rows of a sprite the given number of bits wide on one plane,
+    /// with the leftmost pixel in the highest of those bits.
+    fn draw_rows(
+        &mut self,
+        plane: usize,
+        mut x: u8,
+        mut y: u8,
+        rows: &[u16],
+        bits: u32,
+        on_collision: &mut impl FnMut(u8, u8),
+    ) -> (u8, u32) {
+        let width = self.width() as u8;
+        let height = self.height() as u8;
+        let mut collided_rows = 0;
+        let mut collided_pixels = 0;
 
         let origin_x = x;
         let origin_y = y;
-        for byte in sprite {
-            for i in (0..8).rev() {
-                let bit = (byte >> i) & 0b00000001;
-                if bit == 1 {
-                    if self.draw_pixel(x, y) {
-                        collided = true;
-                    }
+        for row in rows {
+            let mut collided = false;
+            for i in (0..bits).rev() {
+                let bit = (row >> i) & 0b00000001;
+                if bit == 1 && self.toggle_pixel(plane, x, y) {
+                    collided = true;
+                    collided_pixels += 1;
+                    on_collision(x % width, y % height);
                 }
-                x += 1;
+                // Coordinates past 255 wrap around to 0, which is the same
+                // place draw_pixel would wrap them to.
+                x = x.wrapping_add(1);
 
                 // If the sprite starts at the right and gets cut off, break.
-                if origin_x < WIDTH as u8 && x > WIDTH as u8 {
+                if origin_x < width && x > width {
                     break;
                 }
             }
-            y += 1;
+            if collided {
+                collided_rows += 1;
+            }
+            y = y.wrapping_add(1);
             x = origin_x;
 
             // If the sprite starts at the bottom and gets cut off, break.
-            if origin_y < HEIGHT as u8 && y > HEIGHT as u8 {
+            if origin_y < height && y > height {
                 break;
             }
         }
-        collided
+        (collided_rows, collided_pixels)
+    }
+
+    /// What the display would look like after draw_sprite, and whether any
+    /// pixels would collide, without changing this display. For showing the
+    /// effect of a Dxyn before stepping over it.
+    pub fn preview_draw(&self, x: u8, y: u8, sprite: &[u8]) -> (Display, bool) {
+        let mut preview = self.clone();
+        let collided_rows = preview.draw_sprite(x, y, sprite);
+        (preview, collided_rows > 0)
     }
 
-    /// Draws a pixel onto the screen.
+    /// Draws a pixel onto the selected planes.
     /// Returns true if drawing collides with already drawn pixel.
-    pub fn draw_pixel(&mut self, mut x: u8, mut y: u8) -> bool {
+    pub fn draw_pixel(&mut self, x: u8, y: u8) -> bool {
+        let mut collided = false;
+        for plane in self.selected_plane_indices() {
+            collided |= self.toggle_pixel(plane, x, y);
+        }
+        collided
+    }
+
+    /// Flips a pixel on one plane, returning whether it was on.
+    fn toggle_pixel(&mut self, plane: usize, mut x: u8, mut y: u8) -> bool {
+        let width = self.width() as u8;
+        let height = self.height() as u8;
+
         // Loop x and y if they go out of the display's bounds.
-        while x >= WIDTH as u8 {
-            x -= WIDTH as u8;
+        while x >= width {
+            x -= width;
         }
-        while y >= HEIGHT as u8 {
-            y -= HEIGHT as u8;
+        while y >= height {
+            y -= height;
         }
 
-        self.pixels[x as usize] ^= 0b10000000000000000000000000000000 >> y;
+        let mask = 0x8000_0000_0000_0000 >> y;
+        let column = &mut self.planes[plane][x as usize];
+        *column ^= mask;
+        self.dirty = true;
 
         // If the pixel is off, then it collided and this returns true.
-        (self.pixels[x as usize] << y) >> HEIGHT - 1 == 0
+        *column & mask == 0
+    }
+
+    /// A column with a pixel on wherever any plane has one.
+    fn combined_column(&self, x: usize) -> u64 {
+        self.planes.iter().fold(0, |column, plane| column | plane[x])
+    }
+
+    /// The top-left 64x32 pixels packed into one `u32` per column, with the
+    /// most significant bit being the top row. A pixel is on if it is on in
+    /// any plane.
+    pub fn pixels(&self) -> [u32; LOW_RES_HEIGHT * LOW_RES_WIDTH / 32] {
+        self.pixels_with_order(BitOrder::MsbFirst)
+    }
+
+    /// Like pixels, with the top row at the given end of each column.
+    pub fn pixels_with_order(&self, order: BitOrder) -> [u32; LOW_RES_HEIGHT * LOW_RES_WIDTH / 32] {
+        let mut pixels = [0; LOW_RES_HEIGHT * LOW_RES_WIDTH / 32];
+        for (column, pixel) in pixels.iter_mut().enumerate() {
+            let packed = (self.combined_column(column) >> 32) as u32;
+            *pixel = match order {
+                BitOrder::MsbFirst => packed,
+                BitOrder::LsbFirst => packed.reverse_bits(),
+            };
+        }
+        pixels
+    }
+
+    /// Every column of the high resolution grid as two `u32`s, the top half
+    /// then the bottom half, with the most significant bit being the top row.
+    /// This is the layout of the shader's uPixels uniform, which only reads
+    /// as much of it as the current resolution uses. The shader has the one
+    /// foreground color, so a pixel on in any plane is drawn with it.
+    pub fn grid_pixels(&self) -> [u32; GRID_PIXELS_LEN] {
+        let mut pixels = [0; GRID_PIXELS_LEN];
+        for (column, halves) in pixels.chunks_exact_mut(2).enumerate() {
+            let combined = self.combined_column(column);
+            halves[0] = (combined >> 32) as u32;
+            halves[1] = combined as u32;
+        }
+        pixels
+    }
+
+    /// Replaces the contents of the display with a test pattern.
+    pub fn draw_test_pattern(&mut self, pattern: TestPattern) {
+        self.clear_all_planes();
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let on = match pattern {
+                    TestPattern::Solid => true,
+                    TestPattern::Checkerboard => (x + y) % 2 == 0,
+                    TestPattern::Gradient => BAYER_MATRIX[y % 4][x % 4] < x * 17 / self.width(),
+                };
+                if on {
+                    self.draw_pixel(x as u8, y as u8);
+                }
+            }
+        }
+    }
+
+    /// Whether a pixel is on in any plane.
+    pub fn is_pixel_on(&self, x: usize, y: usize) -> bool {
+        self.pixel_planes(x, y) != 0
+    }
+
+    /// Which planes a pixel is on in, as a mask with the first plane as the
+    /// least significant bit. This is the pixel's index into the XO-CHIP
+    /// palette.
+    pub fn pixel_planes(&self, x: usize, y: usize) -> u8 {
+        let mask = 0x8000_0000_0000_0000 >> y;
+        (0..PLANES).filter(|plane| self.planes[*plane][x] & mask != 0).fold(0, |planes, plane| planes | 1 << plane)
+    }
+
+    /// How many pixels are on in any plane, e.g. to tell whether the screen
+    /// is blank.
+    pub fn population_count(&self) -> u32 {
+        (0..HIGH_RES_WIDTH).map(|x| self.combined_column(x).count_ones()).sum()
+    }
+
+    /// How many pixels are on in each XO-CHIP plane. A pixel on both planes
+    /// is counted in both.
+    pub fn plane_population_counts(&self) -> [u32; PLANES] {
+        let mut counts = [0; PLANES];
+        for (count, plane) in counts.iter_mut().zip(self.planes.iter()) {
+            *count = plane.iter().map(|column| column.count_ones()).sum();
+        }
+        counts
+    }
+
+    /// A hash of the resolution and every pixel, stable between runs. The
+    /// second plane is only hashed once something is drawn on it, so
+    /// hashes of Chip-8 and Super Chip-48 screens stay the same.
+    pub fn pixels_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.resolution.hash(&mut hasher);
+        self.planes[0].hash(&mut hasher);
+        if self.planes[1].iter().any(|column| *column != 0) {
+            self.planes[1].hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// The display as 8-bit RGBA pixels, row by row from the top left. Each
+    /// pixel is the palette's color for the planes it is on in.
+    pub fn to_rgba(&self, palette: &Palette) -> Vec<u8> {
+        let to_rgba = |(red, green, blue): (f32, f32, f32)| {
+            [red, green, blue, 1.0].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+        };
+        let colors = [0, 1, 2, 3].map(|planes| to_rgba(palette.color(planes)));
+
+        let mut rgba = Vec::with_capacity(self.width() * self.height() * 4);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                rgba.extend_from_slice(&colors[self.pixel_planes(x, y) as usize]);
+            }
+        }
+        rgba
     }
 
-    pub fn pixels(&self) -> [u32; HEIGHT * WIDTH / 32] {
-        self.pixels
+    /// Like to_rgba, with every Chip-8 pixel drawn as a square of scale by
+    /// scale pixels.
+    pub fn to_rgba_scaled(&self, palette: &Palette, scale: usize) -> Vec<u8> {
+        let rgba = self.to_rgba(palette);
+        let width = self.width() * scale;
+
+        let mut scaled = Vec::with_capacity(rgba.len() * scale * scale);
+        for y in 0..self.height() * scale {
+            for x in 0..width {
+                let pixel = ((y / scale) * self.width() + x / scale) * 4;
+                scaled.extend_from_slice(&rgba[pixel..pixel + 4]);
+            }
+        }
+        scaled
+    }
+
+    /// Saves the display as a png, one pixel per Chip-8 pixel, in the
+    /// palette's four colors like to_rgba.
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P, palette: &Palette) -> Result<(), ScreenshotError> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba(palette))?;
+
+        Ok(())
+    }
+
+    /// Renders the display as one line of characters per row, useful for
+    /// logging the screen where there is no window to look at.
+    pub fn to_ascii(&self, on: char, off: char) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                ascii.push(if self.is_pixel_on(x, y) { on } else { off });
+            }
+            ascii.push('\n');
+        }
+        ascii
     }
 
     pub fn scale(&self) -> u32 {
@@ -104,3 +554,196 @@ impl Display {
         self.foreground_color
     }
 }
+
+/// Somewhere finished frames can be shown, such as a window or a physical LED
+/// matrix. See Chip::set_display_sink.
+pub trait DisplaySink {
+    fn present(&mut self, display: &Display);
+}
+
+/// A sink that calls a function with the x, y and state of every pixel, for
+/// driving hardware one pixel at a time.
+pub struct PixelWriter<F: FnMut(usize, usize, bool)> {
+    write_pixel: F,
+}
+
+impl<F: FnMut(usize, usize, bool)> PixelWriter<F> {
+    pub fn new(write_pixel: F) -> Self {
+        Self { write_pixel }
+    }
+}
+
+impl<F: FnMut(usize, usize, bool)> DisplaySink for PixelWriter<F> {
+    fn present(&mut self, display: &Display) {
+        for y in 0..display.height() {
+            for x in 0..display.width() {
+                (self.write_pixel)(x, y, display.is_pixel_on(x, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display() -> Display {
+        Display::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn clear_turns_off_every_pixel_and_marks_the_display_dirty() {
+        let mut display = display();
+        display.set_resolution(Resolution::High);
+        display.draw_test_pattern(TestPattern::Solid);
+        assert_eq!(display.population_count(), 128 * 64);
+        display.mark_clean();
+
+        display.clear();
+        assert_eq!(display.population_count(), 0);
+        assert!(display.is_dirty());
+    }
+
+    #[test]
+    fn clear_leaves_the_unselected_plane_alone() {
+        let mut display = display();
+        display.select_planes(0b11);
+        display.draw_sprite(0, 0, &[0xF0, 0x0F]);
+        assert_eq!(display.pixel_planes(0, 0), 0b01);
+        assert_eq!(display.pixel_planes(4, 0), 0b10);
+
+        display.select_planes(0b10);
+        display.clear();
+        assert_eq!(display.pixel_planes(0, 0), 0b01);
+        assert_eq!(display.pixel_planes(4, 0), 0);
+        assert_eq!(display.population_count(), 4);
+
+        display.select_planes(0b00);
+        display.clear();
+        assert_eq!(display.population_count(), 4);
+    }
+
+    /// Reads back a png from save_png as RGBA rows.
+    fn load_png(path: &std::path::Path) -> (u32, u32, Vec<u8>) {
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        rgba.truncate(info.buffer_size());
+        (info.width, info.height, rgba)
+    }
+
+    #[test]
+    fn png_uses_the_palette_color_for_each_plane_combination() {
+        let mut display = display();
+        // One pixel on neither plane, the first, the second and both.
+        display.select_planes(0b11);
+        display.draw_sprite(0, 0, &[0b0101_0000, 0b0011_0000]);
+        assert_eq!((0..4).map(|x| display.pixel_planes(x, 0)).collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        let path = std::env::temp_dir().join(format!("chirp-8-png-test-{}.png", std::process::id()));
+        display.save_png(&path, &Palette::OCTO).unwrap();
+        let (width, height, rgba) = load_png(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((width, height), (64, 32));
+        let pixel = |x: usize| &rgba[x * 4..x * 4 + 4];
+        assert_eq!(pixel(0), [0x99, 0x66, 0x00, 0xFF]);
+        assert_eq!(pixel(1), [0xFF, 0xCC, 0x00, 0xFF]);
+        assert_eq!(pixel(2), [0xFF, 0x66, 0x00, 0xFF]);
+        assert_eq!(pixel(3), [0x66, 0x22, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn population_counts_each_plane() {
+        let mut display = display();
+        assert_eq!(display.population_count(), 0);
+        assert_eq!(display.plane_population_counts(), [0, 0]);
+
+        // Three pixels on the first plane, under a row of eight on the
+        // second.
+        display.select_planes(0b11);
+        display.draw_sprite(0, 0, &[0b1110_0000, 0b1111_1111]);
+        assert_eq!(display.plane_population_counts(), [3, 8]);
+        assert_eq!(display.population_count(), 8);
+    }
+
+    #[test]
+    fn to_ascii_draws_a_line_per_row_in_either_resolution() {
+        let mut display = display();
+        // The font's 0.
+        display.draw_sprite(0, 0, &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+
+        let ascii = display.to_ascii('#', '.');
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert!(lines.iter().all(|line| line.len() == 64));
+        let glyph: Vec<&str> = lines[..6].iter().map(|line| &line[..5]).collect();
+        assert_eq!(glyph, ["####.", "#..#.", "#..#.", "#..#.", "####.", "....."]);
+
+        display.set_resolution(Resolution::High);
+        display.draw_sprite(126, 63, &[0xC0]);
+        let ascii = display.to_ascii('#', '.');
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 64);
+        assert!(lines.iter().all(|line| line.len() == 128));
+        assert!(lines[63].ends_with(".##"));
+        assert_eq!(ascii.matches('#').count(), 2);
+    }
+
+    #[test]
+    fn test_patterns_fill_the_expected_pixels() {
+        let mut display = display();
+        display.draw_test_pattern(TestPattern::Solid);
+        assert_eq!(display.population_count(), 64 * 32);
+
+        display.draw_test_pattern(TestPattern::Checkerboard);
+        assert_eq!(display.population_count(), 64 * 32 / 2);
+        assert!(display.is_pixel_on(0, 0) && display.is_pixel_on(1, 1));
+        assert!(!display.is_pixel_on(1, 0) && !display.is_pixel_on(0, 1));
+
+        // The gradient goes from every pixel off on the left to every pixel
+        // on at the right, getting denser in between.
+        display.draw_test_pattern(TestPattern::Gradient);
+        let column = |x| (0..32).filter(|y| display.is_pixel_on(x, *y)).count();
+        assert_eq!(column(0), 0);
+        assert_eq!(column(63), 32);
+        // Each set of four columns covers the whole dither matrix.
+        let block = |block: usize| (block * 4..block * 4 + 4).map(column).sum::<usize>();
+        assert!((1..16).all(|index| block(index) >= block(index - 1)));
+
+        assert_eq!(TestPattern::Solid.next(), Some(TestPattern::Checkerboard));
+        assert_eq!(TestPattern::Checkerboard.next(), Some(TestPattern::Gradient));
+        assert_eq!(TestPattern::Gradient.next(), None);
+    }
+
+    #[test]
+    fn the_top_left_pixel_is_at_the_chosen_end_of_the_first_column() {
+        let mut display = display();
+        display.draw_pixel(0, 0);
+        display.draw_pixel(1, 2);
+
+        let msb_first = display.pixels_with_order(BitOrder::MsbFirst);
+        assert_eq!((msb_first[0], msb_first[1]), (1 << 31, 1 << 29));
+        assert_eq!(display.pixels(), msb_first);
+
+        let lsb_first = display.pixels_with_order(BitOrder::LsbFirst);
+        assert_eq!((lsb_first[0], lsb_first[1]), (1, 1 << 2));
+        assert!(msb_first[2..].iter().chain(&lsb_first[2..]).all(|column| *column == 0));
+    }
+
+    #[test]
+    fn preview_draw_matches_a_real_draw_without_making_it() {
+        let mut display = display();
+        display.draw_sprite(0, 0, &[0xF0]);
+        let before = display.pixels();
+
+        let (preview, collided) = display.preview_draw(2, 0, &[0xF0, 0x90]);
+        assert_eq!(display.pixels(), before);
+
+        let collided_rows = display.draw_sprite(2, 0, &[0xF0, 0x90]);
+        assert_eq!(preview.pixels(), display.pixels());
+        assert_eq!(collided, collided_rows > 0);
+        assert!(collided);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::emulator::Keyboard;
+use gilrs::{Button, EventType};
+use std::collections::HashMap;
+
+/// Maps gamepad buttons onto keys of the Chip-8 keypad.
+pub struct GamepadBindings {
+    buttons: HashMap<Button, u8>,
+}
+
+impl GamepadBindings {
+    /// The D-Pad is bound to the 2/4/6/8 directions most games use, the face
+    /// buttons to 5, A, B and 0, and the shoulder buttons to the paddles of
+    /// Pong (1/4 and C/D).
+    pub fn new() -> Self {
+        let mut buttons = HashMap::new();
+        buttons.insert(Button::DPadUp, 0x2);
+        buttons.insert(Button::DPadLeft, 0x4);
+        buttons.insert(Button::DPadRight, 0x6);
+        buttons.insert(Button::DPadDown, 0x8);
+        buttons.insert(Button::South, 0x5);
+        buttons.insert(Button::East, 0xA);
+        buttons.insert(Button::West, 0xB);
+        buttons.insert(Button::North, 0x0);
+        buttons.insert(Button::LeftTrigger, 0x1);
+        buttons.insert(Button::LeftTrigger2, 0x4);
+        buttons.insert(Button::RightTrigger, 0xC);
+        buttons.insert(Button::RightTrigger2, 0xD);
+        buttons.insert(Button::Select, 0xE);
+        buttons.insert(Button::Start, 0xF);
+        Self { buttons }
+    }
+
+    pub fn bind(&mut self, button: Button, key: u8) {
+        self.buttons.insert(button, key);
+    }
+
+    pub fn unbind(&mut self, button: Button) {
+        self.buttons.remove(&button);
+    }
+
+    pub fn key(&self, button: Button) -> Option<u8> {
+        self.buttons.get(&button).copied()
+    }
+
+    /// Presses or releases the key bound to the event's button, if any.
+    pub fn process_event(&self, event: EventType, keyboard: &mut Keyboard) {
+        match event {
+            EventType::ButtonPressed(button, _) => self.process_button(button, true, keyboard),
+            EventType::ButtonReleased(button, _) => self.process_button(button, false, keyboard),
+            _ => (),
+        }
+    }
+
+    /// Presses or releases the key bound to a button, if any.
+    pub fn process_button(&self, button: Button, pressed: bool, keyboard: &mut Keyboard) {
+        if let Some(key) = self.key(button) {
+            keyboard.set_pressed(key, pressed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Events can't be made outside of gilrs, as their codes are private, so
+    // the buttons they carry are fed in directly.
+    #[test]
+    fn buttons_press_and_release_their_keys() {
+        let mut bindings = GamepadBindings::new();
+        let mut keyboard = Keyboard::new();
+
+        bindings.process_button(Button::DPadUp, true, &mut keyboard);
+        bindings.process_button(Button::RightTrigger, true, &mut keyboard);
+        assert_eq!(keyboard.pressed_mask(), 1 << 0x2 | 1 << 0xC);
+
+        bindings.process_button(Button::DPadUp, false, &mut keyboard);
+        assert_eq!(keyboard.pressed_mask(), 1 << 0xC);
+        assert_eq!(keyboard.just_released(), Some(0x2));
+
+        // Unbound buttons do nothing, and bindings can be changed.
+        bindings.unbind(Button::RightTrigger);
+        bindings.process_button(Button::RightTrigger, false, &mut keyboard);
+        bindings.process_button(Button::Mode, true, &mut keyboard);
+        assert_eq!(keyboard.pressed_mask(), 1 << 0xC);
+
+        bindings.bind(Button::Mode, 0x7);
+        bindings.process_button(Button::Mode, true, &mut keyboard);
+        assert_eq!(keyboard.pressed_mask(), 1 << 0x7 | 1 << 0xC);
+    }
+}
@@ -0,0 +1,131 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InputScriptError {
+    #[error("failed to read the input script")]
+    Io(#[from] std::io::Error),
+    #[error("line {0} of the input script isn't `frame <n>: <key> down|up`: {1}")]
+    InvalidLine(usize, String),
+}
+
+/// The keys to hold down each frame, written by hand for playing a ROM back
+/// the same way every time, e.g. in a test:
+///
+/// ```text
+/// # Hold 2 for five frames, then press 5 and 6 together.
+/// frame 10: 2 down
+/// frame 15: 2 up
+/// frame 20: 5 down
+/// frame 20: 6 down
+/// ```
+///
+/// Keys are a single hex digit, and stay as they were until a later line
+/// changes them. Frame 0 is the first frame run after the script is loaded.
+/// Blank lines and lines starting with `#` are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    /// The keys held during each frame, in the same form as
+    /// Keyboard::pressed_mask.
+    frames: Vec<u16>,
+}
+
+impl InputScript {
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<InputScript, InputScriptError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<InputScript, InputScriptError> {
+        let mut edges = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let edge = parse_edge(line).ok_or(InputScriptError::InvalidLine(index + 1, line.to_string()))?;
+            edges.push(edge);
+        }
+        // Stable, so lines for the same frame keep their order.
+        edges.sort_by_key(|(frame, _, _)| *frame);
+
+        let mut frames = Vec::new();
+        let mut keys = 0;
+        for (frame, key, pressed) in edges {
+            frames.resize(frame, keys);
+            if pressed {
+                keys |= 1 << key;
+            } else {
+                keys &= !(1 << key);
+            }
+        }
+        frames.push(keys);
+
+        Ok(InputScript { frames })
+    }
+
+    /// The keys held during a frame, or None once the script has run out.
+    /// The last frame of a script is the one with its last change.
+    pub fn keys(&self, frame: usize) -> Option<u16> {
+        self.frames.get(frame).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// Parses a `frame <n>: <key> down|up` line into the frame, key and whether
+/// it is pressed.
+fn parse_edge(line: &str) -> Option<(usize, u8, bool)> {
+    let (frame, change) = line.strip_prefix("frame")?.split_once(':')?;
+    let frame = frame.trim().parse().ok()?;
+
+    let mut words = change.split_whitespace();
+    let key = u8::from_str_radix(words.next()?, 16).ok().filter(|key| *key < 16)?;
+    let pressed = match words.next()? {
+        "down" => true,
+        "up" => false,
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some((frame, key, pressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_script_holds_each_key_between_its_edges() {
+        let script = InputScript::parse(
+            "# Hold 2, then press 5 and 6 together.\n\
+             frame 1: 2 down\n\
+             frame 3: 2 up\n\
+             \n\
+             frame 4: 6 down\n\
+             frame 4: 5 down\n",
+        )
+        .unwrap();
+
+        let frames: Vec<_> = (0..script.len()).map(|frame| script.keys(frame).unwrap()).collect();
+        assert_eq!(frames, [0, 1 << 0x2, 1 << 0x2, 0, 1 << 0x5 | 1 << 0x6]);
+        assert_eq!(script.keys(script.len()), None);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected_with_their_line_number() {
+        for line in ["frame x: 2 down", "frame 1: G down", "frame 1: 2 pressed", "frame 1: 2 down now", "2 down"] {
+            let source = format!("frame 0: 1 down\n{}\n", line);
+            assert!(
+                matches!(InputScript::parse(&source), Err(InputScriptError::InvalidLine(2, ref text)) if text == line),
+                "{}",
+                line
+            );
+        }
+    }
+}
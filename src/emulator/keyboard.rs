@@ -1,5 +1,12 @@
 use glutin::event::{ElementState, KeyboardInput, VirtualKeyCode};
 
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
 /// The computers which originally used the Chip-8 Language had a 16-key
 /// hexadecimal keypad with the following layout:
 ///
@@ -13,42 +20,72 @@ use glutin::event::{ElementState, KeyboardInput, VirtualKeyCode};
 pub struct Keyboard {
     keys: [bool; 16],
     just_released: Option<u8>,
+    mirrored: bool,
 }
 
 impl Keyboard {
     pub fn new() -> Self {
-        Self { keys: [false; 16], just_released: None, }
+        Self { keys: [false; 16], just_released: None, mirrored: false, }
     }
 
-    pub fn process_input(&mut self, input: KeyboardInput) {
-        if let Some(keycode) = input.virtual_keycode {
-            let key: Option<u8> = match keycode {
-                VirtualKeyCode::X => Some(0x0),
-                VirtualKeyCode::Key1 => Some(0x1),
-                VirtualKeyCode::Key2 => Some(0x2),
-                VirtualKeyCode::Key3 => Some(0x3),
-                VirtualKeyCode::Q => Some(0x4),
-                VirtualKeyCode::W => Some(0x5),
-                VirtualKeyCode::E => Some(0x6),
-                VirtualKeyCode::A => Some(0x7),
-                VirtualKeyCode::S => Some(0x8),
-                VirtualKeyCode::D => Some(0x9),
-                VirtualKeyCode::Z => Some(0xA),
-                VirtualKeyCode::C => Some(0xB),
-                VirtualKeyCode::Key4 => Some(0xC),
-                VirtualKeyCode::R => Some(0xD),
-                VirtualKeyCode::F => Some(0xE),
-                VirtualKeyCode::V => Some(0xF),
-                _ => None
-            };
-            
-            if let Some(key) = key {
-                self.keys[key as usize] = input.state == ElementState::Pressed;
-                if input.state == ElementState::Released {
-                    self.just_released = Some(key);
-                }
+    /// Swaps the left and right columns of the keypad layout, for left-handed
+    /// players. Calling this again restores the original layout.
+    pub fn mirror_horizontal(&mut self) {
+        self.mirrored = !self.mirrored;
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        self.mirrored
+    }
+
+    fn mirror_key(key: u8) -> u8 {
+        for row in KEYPAD_LAYOUT.iter() {
+            if let Some(column) = row.iter().position(|k| *k == key) {
+                return row[row.len() - 1 - column];
             }
         }
+        key
+    }
+
+    pub fn process_input(&mut self, input: KeyboardInput) {
+        if let Some(key) = input.virtual_keycode.and_then(|keycode| self.keypad_key(keycode)) {
+            self.set_pressed(key, input.state == ElementState::Pressed);
+        }
+    }
+
+    /// The keypad key a keyboard key is mapped to, if any, taking mirroring
+    /// into account.
+    pub fn keypad_key(&self, keycode: VirtualKeyCode) -> Option<u8> {
+        let key: Option<u8> = match keycode {
+            VirtualKeyCode::X => Some(0x0),
+            VirtualKeyCode::Key1 => Some(0x1),
+            VirtualKeyCode::Key2 => Some(0x2),
+            VirtualKeyCode::Key3 => Some(0x3),
+            VirtualKeyCode::Q => Some(0x4),
+            VirtualKeyCode::W => Some(0x5),
+            VirtualKeyCode::E => Some(0x6),
+            VirtualKeyCode::A => Some(0x7),
+            VirtualKeyCode::S => Some(0x8),
+            VirtualKeyCode::D => Some(0x9),
+            VirtualKeyCode::Z => Some(0xA),
+            VirtualKeyCode::C => Some(0xB),
+            VirtualKeyCode::Key4 => Some(0xC),
+            VirtualKeyCode::R => Some(0xD),
+            VirtualKeyCode::F => Some(0xE),
+            VirtualKeyCode::V => Some(0xF),
+            _ => None
+        };
+
+        if self.mirrored { key.map(Self::mirror_key) } else { key }
+    }
+
+    /// Presses or releases a key directly, for input that doesn't come from
+    /// the keyboard.
+    pub fn set_pressed(&mut self, key: u8, pressed: bool) {
+        self.keys[key as usize & 0xF] = pressed;
+        if !pressed {
+            self.just_released = Some(key & 0xF);
+        }
     }
 
     pub fn is_pressed(&self, key: u8) -> bool {
@@ -60,6 +97,26 @@ impl Keyboard {
         self.keys[key as usize]
     }
 
+    /// Which keys are pressed, one bit per key with key 0 being the least
+    /// significant bit.
+    pub fn pressed_mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, pressed)| mask | (*pressed as u16) << key)
+    }
+
+    /// Presses and releases keys to match a mask from pressed_mask. Only keys
+    /// that change are touched, so just_released stays accurate.
+    pub fn set_pressed_mask(&mut self, mask: u16) {
+        for key in 0..16 {
+            let pressed = mask & (1 << key) != 0;
+            if self.keys[key as usize] != pressed {
+                self.set_pressed(key, pressed);
+            }
+        }
+    }
+
     pub fn just_released(&self) -> Option<u8> {
         self.just_released
     }
@@ -68,3 +125,26 @@ impl Keyboard {
         self.just_released = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirroring_swaps_the_columns_and_is_its_own_inverse() {
+        let mut keyboard = Keyboard::new();
+        keyboard.mirror_horizontal();
+        assert!(keyboard.is_mirrored());
+        assert_eq!(keyboard.keypad_key(VirtualKeyCode::Key1), Some(0xC));
+        assert_eq!(keyboard.keypad_key(VirtualKeyCode::Key4), Some(0x1));
+        assert_eq!(keyboard.keypad_key(VirtualKeyCode::W), Some(0x6));
+        assert_eq!(keyboard.keypad_key(VirtualKeyCode::X), Some(0xB));
+        assert_eq!(keyboard.keypad_key(VirtualKeyCode::P), None);
+
+        keyboard.mirror_horizontal();
+        assert!(!keyboard.is_mirrored());
+        let layout = [VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4];
+        let keys: Vec<_> = layout.iter().filter_map(|keycode| keyboard.keypad_key(*keycode)).collect();
+        assert_eq!(keys, KEYPAD_LAYOUT[0]);
+    }
+}
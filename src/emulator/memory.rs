@@ -1,4 +1,30 @@
-const PROGRAM_OFFSET: u16 = 512;
+use crate::emulator::sprites::{FONT_LEN, LARGE_FONT_LEN, LARGE_FONT_OFFSET};
+use thiserror::Error;
+
+pub const PROGRAM_OFFSET: u16 = 512;
+
+/// A ROM that doesn't fit between the program start and the end of memory.
+/// ROMs this large are rejected rather than cut short or wrapped around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("the rom is {len} bytes, but only {max_len} fit in memory")]
+pub struct RomTooLarge {
+    pub len: usize,
+    pub max_len: usize,
+}
+
+impl From<RomTooLarge> for std::io::Error {
+    fn from(err: RomTooLarge) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// A labelled stretch of memory, from start up to, but not including, end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+    pub label: &'static str,
+}
 
 /// The Chip-8 language is capable of accessing up to 4KB (4,096 bytes) of RAM,
 /// from location 0x000 (0) to 0xFFF (4095). The first 512 bytes, from 0x000
@@ -27,29 +53,148 @@ const PROGRAM_OFFSET: u16 = 512;
 /// | Reserved for  |
 /// |  interpreter  |
 /// +---------------+= 0x000 (0) Start of Chip-8 RAM
-#[derive(Debug)]
+///
+/// XO-CHIP extends this to 64KB, using all 16 bits of I for addresses.
+#[derive(Debug, Clone)]
 pub struct Memory {
-    ram: [u8; 4096],
+    ram: Vec<u8>,
+    address_bits: u8,
+    rom_len: usize,
 }
 
 impl Memory {
     pub fn new() -> Self {
-        let mut memory = Self { ram: [0; 4096] };
+        let mut memory = Self { ram: vec![0; 0x10000], address_bits: 12, rom_len: 0 };
         crate::emulator::sprites::load_default_sprites(&mut memory);
         memory
     }
 
+    /// Memory with a custom font in place of the default one, for different
+    /// glyph styles.
+    pub fn with_font(font: &[u8; FONT_LEN]) -> Self {
+        let mut memory = Self::new();
+        memory.set_font(font);
+        memory
+    }
+
+    /// Replaces the 16 font characters used by Fx29, 5 bytes each.
+    pub fn set_font(&mut self, font: &[u8; FONT_LEN]) {
+        for (i, byte) in font.iter().enumerate() {
+            self.write(i as u16, *byte);
+        }
+    }
+
+    /// Replaces the 10 large digits used by Fx30, 10 bytes each.
+    pub fn set_large_font(&mut self, font: &[u8; LARGE_FONT_LEN]) {
+        for (i, byte) in font.iter().enumerate() {
+            self.write(LARGE_FONT_OFFSET + i as u16, *byte);
+        }
+    }
+
+    pub fn address_bits(&self) -> u8 {
+        self.address_bits
+    }
+
+    /// Sets whether addresses are 12 bits (4KB, Chip-8) or 16 bits (64KB,
+    /// XO-CHIP) wide. Addresses outside of the range wrap around.
+    pub fn set_address_bits(&mut self, address_bits: u8) {
+        assert!(address_bits == 12 || address_bits == 16, "address bits must be 12 or 16, not {}", address_bits);
+        self.address_bits = address_bits;
+    }
+
+    pub fn address_mask(&self) -> u16 {
+        (u32::MAX >> (32 - self.address_bits)) as u16
+    }
+
     pub fn read(&self, address: u16) -> u8 {
-        self.ram[address as usize]
+        self.ram[(address & self.address_mask()) as usize]
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
+        let address = address & self.address_mask();
         self.ram[address as usize] = value;
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        for (i, byte) in rom_data.iter().enumerate() {
-            self.write(PROGRAM_OFFSET + (i as u16), *byte);
+    /// The length of the most recently loaded ROM.
+    pub fn rom_len(&self) -> usize {
+        self.rom_len
+    }
+
+    /// What each part of memory is used for, in order, for showing a memory
+    /// map. Empty regions are left out.
+    pub fn regions(&self) -> Vec<MemoryRegion> {
+        let large_font_end = LARGE_FONT_OFFSET as usize + LARGE_FONT_LEN;
+        let rom_end = (PROGRAM_OFFSET as usize + self.rom_len).min(self.ram_len());
+        let regions = vec![
+            MemoryRegion { start: 0, end: FONT_LEN, label: "Font" },
+            MemoryRegion { start: FONT_LEN, end: large_font_end, label: "Large Font" },
+            MemoryRegion { start: large_font_end, end: PROGRAM_OFFSET as usize, label: "Interpreter" },
+            MemoryRegion { start: PROGRAM_OFFSET as usize, end: rom_end, label: "ROM" },
+            MemoryRegion { start: rom_end, end: self.ram_len(), label: "Free" },
+        ];
+        regions.into_iter().filter(|region| region.start < region.end).collect()
+    }
+
+    /// The largest ROM that fits between the program start and the end of
+    /// memory.
+    pub fn max_rom_len(&self) -> usize {
+        self.ram_len() - PROGRAM_OFFSET as usize
+    }
+
+    /// How many bytes are addressable with the current address bits.
+    fn ram_len(&self) -> usize {
+        self.address_mask() as usize + 1
+    }
+
+    /// Zeroes everything from the program start to the end of memory, leaving
+    /// the fonts in place.
+    pub fn clear_program(&mut self) {
+        self.ram[PROGRAM_OFFSET as usize..].fill(0);
+        self.rom_len = 0;
+    }
+
+    /// Copies a ROM in at PROGRAM_OFFSET, clearing what was left behind by
+    /// any ROM loaded before it.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), RomTooLarge> {
+        let max_len = self.max_rom_len();
+        if rom_data.len() > max_len {
+            return Err(RomTooLarge { len: rom_data.len(), max_len });
         }
+
+        self.clear_program();
+        self.rom_len = rom_data.len();
+        let start = PROGRAM_OFFSET as usize;
+        self.ram[start..start + rom_data.len()].copy_from_slice(rom_data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_rom_region_covers_the_loaded_rom() {
+        let mut memory = Memory::new();
+        let labels = |memory: &Memory| memory.regions().iter().map(|region| region.label).collect::<Vec<_>>();
+        assert_eq!(labels(&memory), ["Font", "Large Font", "Interpreter", "Free"]);
+
+        memory.load_rom(include_bytes!("../../roms/tests/2-ibm-logo.ch8")).unwrap();
+        let regions = memory.regions();
+        assert_eq!(regions[3], MemoryRegion { start: 0x200, end: 0x200 + 132, label: "ROM" });
+        assert_eq!(regions[4], MemoryRegion { start: 0x200 + 132, end: 0x1000, label: "Free" });
+        assert_eq!(regions[0].start, 0);
+        assert!(regions.windows(2).all(|pair| pair[0].end == pair[1].start));
+    }
+
+    #[test]
+    fn a_short_rom_leaves_nothing_of_a_longer_one_behind() {
+        let mut memory = Memory::new();
+        memory.load_rom(&[0xAA; 246]).unwrap();
+        memory.load_rom(&[0x12, 0x00]).unwrap();
+
+        assert_eq!((memory.read(PROGRAM_OFFSET), memory.read(PROGRAM_OFFSET + 1)), (0x12, 0x00));
+        assert!((PROGRAM_OFFSET + 2..PROGRAM_OFFSET + 246).all(|address| memory.read(address) == 0));
+        assert_eq!(memory.rom_len(), 2);
     }
 }
@@ -1,19 +1,63 @@
+pub mod beep;
+
 mod chip;
 pub use chip::*;
 
+pub mod disasm;
+
 mod display;
 pub use display::*;
 
+#[cfg(feature = "gilrs")]
+mod gamepad;
+#[cfg(feature = "gilrs")]
+pub use gamepad::*;
+
+mod input_script;
+pub use input_script::*;
+
 mod keyboard;
 pub use keyboard::*;
 
+mod recording;
+pub use recording::*;
+
 mod registers;
 pub use registers::*;
 
+mod rng;
+pub use rng::*;
+
 mod memory;
 pub use memory::*;
 
+mod pacer;
+pub use pacer::*;
+
+mod palette;
+pub use palette::*;
+
+mod quirks;
+pub use quirks::*;
+
+mod quirks_test;
+pub use quirks_test::*;
+
+mod save_state;
+pub use save_state::*;
+
+mod settings_menu;
+pub use settings_menu::*;
+
+mod sidecar;
+pub use sidecar::*;
+
 pub mod sprites;
 
+#[cfg(feature = "threaded")]
+mod threaded;
+#[cfg(feature = "threaded")]
+pub use threaded::*;
+
 mod timers;
 pub use timers::*;
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+/// After a stall longer than this many cycles, such as while the window is
+/// being dragged, the rest of the missed time is dropped rather than run all
+/// at once.
+const MAX_CATCH_UP_CYCLES: u32 = 6;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Works out how many cycles are due from the real time that has passed, for
+/// a front-end that polls more or less often than the cycles run. Time left
+/// over from each call is carried to the next, in nanoseconds times the rate
+/// so nothing is lost to rounding, so on average exactly `rate_hz` cycles
+/// run a second however unevenly the front-end polls.
+pub struct FramePacer {
+    rate_hz: u32,
+    carried: u128,
+}
+
+impl FramePacer {
+    pub fn new(rate_hz: u32) -> Self {
+        Self { rate_hz: rate_hz.max(1), carried: 0 }
+    }
+
+    /// Adds the time since the last call, and returns how many cycles to run
+    /// for it.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.carried += elapsed.as_nanos() * self.rate_hz as u128;
+        let due = self.carried / NANOS_PER_SECOND;
+        self.carried %= NANOS_PER_SECOND;
+
+        if due > MAX_CATCH_UP_CYCLES as u128 {
+            self.carried = 0;
+            return MAX_CATCH_UP_CYCLES;
+        }
+        due as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{Chip, ChipRng, Quirks, RngKind};
+
+    /// ADD V1, 1 then JP 0x200, forever.
+    const COUNTING_LOOP: [u8; 4] = [0x71, 0x01, 0x12, 0x00];
+
+    /// A mock clock for a front-end polling at 144Hz, each poll up to a
+    /// millisecond early or late.
+    fn jittery_polls(seconds: u32) -> Vec<Duration> {
+        let mut rng = ChipRng::new(RngKind::Xorshift(144));
+        let poll = Duration::from_secs(1) / 144;
+        (0..seconds * 144)
+            .map(|_| poll - Duration::from_millis(1) + Duration::from_micros(rng.next_byte() as u64 * 2000 / 255))
+            .collect()
+    }
+
+    #[test]
+    fn timer_ticks_average_60_a_second_at_an_awkward_clock_speed() {
+        let mut chip = Chip::headless(Quirks::new());
+        chip.load_rom(&COUNTING_LOOP).unwrap();
+        chip.set_clock_hz(701);
+        let mut pacer = FramePacer::new(60);
+
+        let mut elapsed = Duration::ZERO;
+        let mut second_start_ticks = 0;
+        for poll in jittery_polls(20) {
+            let second = elapsed.as_secs();
+            for _ in 0..pacer.advance(poll) {
+                chip.cpu_cycle().unwrap();
+            }
+            elapsed += poll;
+
+            // No second gets more than a tick more or less than 60.
+            if elapsed.as_secs() != second {
+                let ticks = chip.cycles() - second_start_ticks;
+                assert!((59..=61).contains(&ticks), "{} ticks in second {}", ticks, second);
+                second_start_ticks = chip.cycles();
+            }
+        }
+
+        let expected = elapsed.as_secs_f64() * 60.0;
+        assert!((chip.cycles() as f64 - expected).abs() <= 1.0, "{} ticks in {:?}", chip.cycles(), elapsed);
+    }
+
+    #[test]
+    fn nothing_is_lost_to_rounding() {
+        // A 60th of a second is 16,666,666.67ns, so a cycle is due after
+        // 16,666,667ns and not a nanosecond before.
+        let mut pacer = FramePacer::new(60);
+        assert_eq!((0..3).map(|_| pacer.advance(Duration::from_nanos(5_555_555))).sum::<u32>(), 0);
+        assert_eq!(pacer.advance(Duration::from_nanos(1)), 0);
+        assert_eq!(pacer.advance(Duration::from_nanos(1)), 1);
+    }
+
+    #[test]
+    fn a_long_stall_is_only_caught_up_so_far() {
+        let mut pacer = FramePacer::new(60);
+        assert_eq!(pacer.advance(Duration::from_secs(5)), MAX_CATCH_UP_CYCLES);
+        assert_eq!(pacer.advance(Duration::from_millis(1)), 0);
+    }
+}
@@ -0,0 +1,210 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PaletteError {
+    #[error("failed to read the palette file")]
+    Io(#[from] std::io::Error),
+    #[error("line {0} of the palette file isn't a #RRGGBB color: {1}")]
+    InvalidColor(usize, String),
+    #[error("palette files need 2 to 4 colors, not {0}")]
+    ColorCount(usize),
+}
+
+/// Palette files can have up to this many colors, one for each XO-CHIP plane
+/// combination.
+const MAX_PALETTE_FILE_COLORS: usize = 4;
+
+/// The colors the display is drawn with. XO-CHIP's two planes pick one of
+/// four: the background where neither is on, the foreground for the first
+/// plane, and a color each for the second plane and for both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: (f32, f32, f32),
+    pub foreground: (f32, f32, f32),
+    pub second_plane: (f32, f32, f32),
+    pub both_planes: (f32, f32, f32),
+}
+
+impl Palette {
+    pub const WHITE: Palette = Palette {
+        background: (0.0, 0.0, 0.0),
+        foreground: (1.0, 1.0, 1.0),
+        second_plane: (0.67, 0.67, 0.67),
+        both_planes: (0.33, 0.33, 0.33),
+    };
+    pub const GREEN: Palette = Palette {
+        background: (0.0, 0.05, 0.0),
+        foreground: (0.2, 1.0, 0.2),
+        second_plane: (0.1, 0.6, 0.1),
+        both_planes: (0.05, 0.3, 0.05),
+    };
+    pub const AMBER: Palette = Palette {
+        background: (0.05, 0.02, 0.0),
+        foreground: (1.0, 0.69, 0.0),
+        second_plane: (0.7, 0.35, 0.0),
+        both_planes: (0.35, 0.17, 0.0),
+    };
+    /// The default colors of the Octo Chip-8 IDE.
+    pub const OCTO: Palette = Palette {
+        background: (0.6, 0.4, 0.0),
+        foreground: (1.0, 0.8, 0.0),
+        second_plane: (1.0, 0.4, 0.0),
+        both_planes: (0.4, 0.133, 0.0),
+    };
+
+    pub const PRESETS: [Palette; 4] = [Palette::WHITE, Palette::GREEN, Palette::AMBER, Palette::OCTO];
+
+    /// Looks up a preset by its name, ignoring case.
+    pub fn preset(name: &str) -> Option<Palette> {
+        match name.to_lowercase().as_str() {
+            "white" => Some(Palette::WHITE),
+            "green" => Some(Palette::GREEN),
+            "amber" => Some(Palette::AMBER),
+            "octo" => Some(Palette::OCTO),
+            _ => None,
+        }
+    }
+
+    /// The name of the preset this palette is, the reverse of preset.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        let names = ["white", "green", "amber", "octo"];
+        Self::PRESETS.iter().position(|preset| preset == self).map(|index| names[index])
+    }
+
+    /// Reads a palette from a file of #RRGGBB colors, one per line, such as
+    /// those exported by Lospec. Blank lines are skipped. The first color is
+    /// the background and the second the foreground. Up to two more colors
+    /// can be given for the second XO-CHIP plane and for both planes, which
+    /// are the foreground when left out.
+    pub fn from_hex_file<P: AsRef<std::path::Path>>(path: P) -> Result<Palette, PaletteError> {
+        Self::from_hex(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of a palette file. See from_hex_file.
+    pub fn from_hex(source: &str) -> Result<Palette, PaletteError> {
+        let mut colors = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let color = parse_hex_color(line).ok_or(PaletteError::InvalidColor(index + 1, line.to_string()))?;
+            colors.push(color);
+        }
+
+        if colors.len() < 2 || colors.len() > MAX_PALETTE_FILE_COLORS {
+            return Err(PaletteError::ColorCount(colors.len()));
+        }
+        Ok(Palette {
+            background: colors[0],
+            foreground: colors[1],
+            second_plane: colors.get(2).copied().unwrap_or(colors[1]),
+            both_planes: colors.get(3).copied().unwrap_or(colors[1]),
+        })
+    }
+
+    /// The preset after this one, wrapping back around to the first. Palettes
+    /// that aren't presets go to the first preset.
+    pub fn next_preset(&self) -> Palette {
+        match Self::PRESETS.iter().position(|preset| preset == self) {
+            Some(index) => Self::PRESETS[(index + 1) % Self::PRESETS.len()],
+            None => Self::PRESETS[0],
+        }
+    }
+
+    /// The palette with every color converted to gray, using the same
+    /// Rec. 709 luma weights as the shader.
+    pub fn to_grayscale(self) -> Palette {
+        let gray = |color| {
+            let luminance = luminance(color);
+            (luminance, luminance, luminance)
+        };
+        Palette {
+            background: gray(self.background),
+            foreground: gray(self.foreground),
+            second_plane: gray(self.second_plane),
+            both_planes: gray(self.both_planes),
+        }
+    }
+
+    /// The color for a mask of the planes a pixel is on, as given by
+    /// Display::pixel_planes.
+    pub fn color(&self, planes: u8) -> (f32, f32, f32) {
+        match planes & 0b11 {
+            0b00 => self.background,
+            0b01 => self.foreground,
+            0b10 => self.second_plane,
+            _ => self.both_planes,
+        }
+    }
+}
+
+/// How bright a color looks, from 0 to 1.
+pub fn luminance((red, green, blue): (f32, f32, f32)) -> f32 {
+    0.2126 * red + 0.7152 * green + 0.0722 * blue
+}
+
+/// Parses a #RRGGBB color into channels from 0 to 1.
+fn parse_hex_color(hex: &str) -> Option<(f32, f32, f32)> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 || !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |start: usize| u8::from_str_radix(&digits[start..start + 2], 16).ok().map(|value| value as f32 / 255.0);
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_palette_colors_the_xo_chip_planes() {
+        let palette = Palette::from_hex("#996600\n#FFCC00\n#FF6600\n#662200\n").unwrap();
+        assert_eq!(palette.color(0b10), (1.0, 0.4, 0.0));
+        assert_eq!(palette.color(0b11), (0.4, 0.13333334, 0.0));
+
+        let two_colors = Palette::from_hex("#000000\n#FFFFFF\n").unwrap();
+        assert_eq!(two_colors.second_plane, two_colors.foreground);
+        assert_eq!(two_colors.both_planes, two_colors.foreground);
+    }
+
+    #[test]
+    fn next_preset_cycles_through_every_preset() {
+        let mut palette = Palette::WHITE;
+        let mut seen = Vec::new();
+        for _ in 0..Palette::PRESETS.len() {
+            palette = palette.next_preset();
+            seen.push(palette);
+        }
+        assert_eq!(seen, [Palette::GREEN, Palette::AMBER, Palette::OCTO, Palette::WHITE]);
+
+        let custom = Palette::from_hex("#123456\n#654321\n").unwrap();
+        assert_eq!(custom.next_preset(), Palette::WHITE);
+    }
+
+    #[test]
+    fn a_hex_file_is_read_into_a_palette() {
+        let path = std::env::temp_dir().join(format!("chirp-8-palette-test-{}.hex", std::process::id()));
+        std::fs::write(&path, "#000000\n\n#FFFFFF\n#FF0000\n#00FF00\n").unwrap();
+        let palette = Palette::from_hex_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let palette = palette.unwrap();
+        assert_eq!(palette.background, (0.0, 0.0, 0.0));
+        assert_eq!(palette.foreground, (1.0, 1.0, 1.0));
+        assert_eq!(palette.second_plane, (1.0, 0.0, 0.0));
+        assert_eq!(palette.both_planes, (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn malformed_hex_palettes_are_rejected() {
+        assert!(matches!(Palette::from_hex("#000000\nFFFFFF\n"), Err(PaletteError::InvalidColor(2, _))));
+        assert!(matches!(Palette::from_hex("#000000\n#FFFFF\n"), Err(PaletteError::InvalidColor(2, _))));
+        assert!(matches!(Palette::from_hex("#000000\n#GGGGGG\n"), Err(PaletteError::InvalidColor(2, _))));
+        assert!(matches!(Palette::from_hex("#000000\n"), Err(PaletteError::ColorCount(1))));
+        assert!(matches!(Palette::from_hex(&"#000000\n".repeat(5)), Err(PaletteError::ColorCount(5))));
+        assert!(matches!(Palette::from_hex_file("/nonexistent/palette.hex"), Err(PaletteError::Io(_))));
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// What to do with instructions that were added by Super Chip-48, such as
+/// 00FE and 00FF, for running ROMs as plain Chip-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchipPolicy {
+    /// Stop with an error.
+    Error,
+    /// Skip over them as if they weren't there.
+    Ignore,
+    Enable,
+}
+
+/// Chip-8 interpreters have never fully agreed on the behaviour of a handful
+/// of instructions, and some ROMs depend on the behaviour of the interpreter
+/// they were written for. Quirks toggle between these behaviours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default = "Quirks::new")]
+pub struct Quirks {
+    /// On SCHIP, drawing in high resolution mode sets VF to the number of
+    /// sprite rows that collided with already drawn pixels, rather than 1.
+    pub schip_collision_count: bool,
+    /// Some interpreters don't wrap a sprite's starting coordinate around the
+    /// screen, so a sprite that starts off-screen isn't drawn at all.
+    pub no_coord_wrap: bool,
+    /// Not a quirk of any interpreter, but warns whenever a jump or call lands
+    /// below the program, in the font and interpreter's memory. This is almost
+    /// always a bug in the ROM or the loader.
+    pub warn_low_jumps: bool,
+    /// Dxyn normally wraps around to the start of memory when I + n runs past
+    /// the end. With this, the rows past the end aren't drawn, and a warning
+    /// is sent.
+    pub clamp_sprite_reads: bool,
+    /// Super Chip-48 instructions are enabled by default.
+    pub schip_opcodes: SchipPolicy,
+}
+
+impl Quirks {
+    pub fn new() -> Self {
+        Self {
+            schip_collision_count: false,
+            no_coord_wrap: false,
+            warn_low_jumps: false,
+            clamp_sprite_reads: false,
+            schip_opcodes: SchipPolicy::Enable,
+        }
+    }
+}
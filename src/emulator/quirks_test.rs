@@ -0,0 +1,116 @@
+use crate::emulator::{Chip, Display, Quirks};
+
+/// Which platform's expectations the quirks test ROM checks against. The ROM
+/// normally asks with a menu, but skips it when one of these is at 0x1FF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksTestPlatform {
+    Chip8 = 1,
+    Schip = 2,
+    XoChip = 3,
+}
+
+/// Where the quirks test ROM asks for its platform.
+const PLATFORM_ADDRESS: u16 = 0x1FF;
+
+/// Long enough for the ROM to finish its checks and draw every result.
+const QUIRKS_TEST_CYCLES: usize = 600;
+
+/// Each result is a line of text 5 pixels tall, with a 3x3 tick or cross
+/// next to it. The icon for the first line starts at (ICON_X, ICON_Y).
+const LINE_HEIGHT: usize = 5;
+const ICON_X: usize = 59;
+const ICON_Y: usize = 2;
+const TICK: [[bool; 3]; 3] = [[true, false, true], [true, true, false], [true, false, false]];
+const CROSS: [[bool; 3]; 3] = [[true, false, true], [false, true, false], [true, false, true]];
+
+/// Whether each quirk matched the platform's expectations in Timendus' quirks
+/// test ROM, in the order the ROM shows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuirksTestResult {
+    /// 8xy1, 8xy2 and 8xy3 reset VF.
+    pub vf_reset: bool,
+    /// Fx55 and Fx65 increment I.
+    pub memory: bool,
+    /// Dxyn waits for the start of a frame.
+    pub display_wait: bool,
+    /// Sprites are clipped at the edges of the screen rather than wrapped.
+    pub clipping: bool,
+    /// 8xy6 and 8xyE shift Vx rather than Vy.
+    pub shifting: bool,
+    /// Bnnn jumps to nnn + Vx rather than V0.
+    pub jumping: bool,
+}
+
+impl QuirksTestResult {
+    /// Reads the ticks and crosses off the quirks test ROM's results screen.
+    /// Returns None if any of them can't be found, such as when the ROM
+    /// hasn't finished.
+    pub fn parse(display: &Display) -> Option<Self> {
+        let line = |index: usize| -> Option<bool> {
+            let top = index * LINE_HEIGHT + ICON_Y;
+            let mut icon = [[false; 3]; 3];
+            for (y, row) in icon.iter_mut().enumerate() {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    *pixel = display.is_pixel_on(ICON_X + x, top + y);
+                }
+            }
+
+            match icon {
+                TICK => Some(true),
+                CROSS => Some(false),
+                _ => None,
+            }
+        };
+
+        Some(Self {
+            vf_reset: line(0)?,
+            memory: line(1)?,
+            display_wait: line(2)?,
+            clipping: line(3)?,
+            shifting: line(4)?,
+            jumping: line(5)?,
+        })
+    }
+
+    /// Whether every quirk matched.
+    pub fn all_passed(&self) -> bool {
+        self.vf_reset && self.memory && self.display_wait && self.clipping && self.shifting && self.jumping
+    }
+}
+
+/// Runs the quirks test ROM headless with the given quirks, and reads which
+/// of them match the platform. Returns None if the ROM stops with an error or
+/// its results can't be read.
+pub fn run_quirks_test(rom_data: &[u8], quirks: Quirks, platform: QuirksTestPlatform) -> Option<QuirksTestResult> {
+    let mut chip = Chip::headless(quirks);
+    chip.load_rom(rom_data).ok()?;
+    chip.memory_mut().write(PLATFORM_ADDRESS, platform as u8);
+
+    for _ in 0..QUIRKS_TEST_CYCLES {
+        chip.run_frame().ok()?;
+    }
+
+    QuirksTestResult::parse(chip.latest_frame())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIRKS_TEST_ROM: &[u8] = include_bytes!("../../roms/tests/5-quirks.ch8");
+
+    #[test]
+    fn the_default_quirks_pass_every_chip8_check() {
+        let result = run_quirks_test(QUIRKS_TEST_ROM, Quirks::new(), QuirksTestPlatform::Chip8).unwrap();
+        assert_eq!(
+            result,
+            QuirksTestResult { vf_reset: true, memory: true, display_wait: true, clipping: true, shifting: true, jumping: true }
+        );
+        assert!(result.all_passed());
+    }
+
+    #[test]
+    fn an_unfinished_results_screen_isnt_parsed() {
+        assert_eq!(QuirksTestResult::parse(&Display::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0))), None);
+    }
+}
@@ -0,0 +1,28 @@
+use crate::emulator::Quirks;
+
+/// The keys held down at the start of a frame, and the hash of the display at
+/// the end of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedFrame {
+    /// One bit per key, with key 0 being the least significant bit.
+    pub keys: u16,
+    pub display_hash: u64,
+}
+
+/// A session that can be played back frame by frame, made with
+/// Chip::start_recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub rom: Vec<u8>,
+    pub quirks: Quirks,
+    pub clock_hz: u32,
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// The first frame of a replay where the display didn't match the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DivergenceReport {
+    pub frame: usize,
+    pub expected_hash: u64,
+    pub actual_hash: u64,
+}
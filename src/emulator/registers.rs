@@ -10,6 +10,7 @@
 /// programs. The program counter (PC) should be 16-bit, and is used to store
 /// the currently executing address. The stack pointer (SP) can be 8-bit, it is
 /// used to point to the topmost level of the stack.
+#[derive(Clone)]
 pub struct Registers {
     v: [u8; 16],
     i: u16,
@@ -62,7 +63,12 @@ impl Registers {
     }
 
     pub fn increment_pc(&mut self) {
-        self.pc += 2;
+        self.pc = self.pc.wrapping_add(2);
+    }
+
+    /// The return addresses on the stack, with the top of the stack last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
     }
 
     pub fn push_stack(&mut self, value: u16) {
@@ -0,0 +1,121 @@
+use rand::Rng;
+
+/// Which generator RND draws its random bytes from. The seeded generators
+/// are implemented here so that their sequences never change, which makes
+/// them useful for matching another emulator's randomness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RngKind {
+    /// Properly random, seeded by the operating system. The default.
+    Entropy,
+    /// Marsaglia's 64-bit xorshift, from the given seed.
+    Xorshift(u64),
+    /// The 32-bit PCG generator, from the given seed.
+    Pcg(u64),
+    /// The given bytes in order, starting again from the first after the
+    /// last. An empty sequence always gives 0.
+    Fixed(Vec<u8>),
+}
+
+/// The Xorshift state can never be 0, so a seed of 0 starts here instead.
+const XORSHIFT_ZERO_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_INCREMENT: u64 = 1442695040888963407;
+
+/// A random byte generator of one of the RngKinds.
+#[derive(Debug, Clone)]
+pub struct ChipRng {
+    kind: RngKind,
+    state: u64,
+}
+
+impl ChipRng {
+    pub fn new(kind: RngKind) -> Self {
+        let state = match kind {
+            RngKind::Entropy | RngKind::Fixed(_) => 0,
+            RngKind::Xorshift(0) => XORSHIFT_ZERO_SEED,
+            RngKind::Xorshift(seed) => seed,
+            RngKind::Pcg(seed) => {
+                // The seeding from the reference implementation.
+                let state = PCG_INCREMENT.wrapping_add(seed);
+                state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(PCG_INCREMENT)
+            },
+        };
+
+        Self { kind, state }
+    }
+
+    pub fn kind(&self) -> &RngKind {
+        &self.kind
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        match &self.kind {
+            RngKind::Entropy => rand::thread_rng().gen(),
+            RngKind::Xorshift(_) => {
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 7;
+                self.state ^= self.state << 17;
+                (self.state >> 56) as u8
+            },
+            RngKind::Pcg(_) => {
+                let old = self.state;
+                self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(PCG_INCREMENT);
+                let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+                let rotation = (old >> 59) as u32;
+                (xorshifted.rotate_right(rotation) >> 24) as u8
+            },
+            RngKind::Fixed(bytes) => {
+                let byte = bytes.get(self.state as usize % bytes.len().max(1)).copied().unwrap_or(0);
+                self.state += 1;
+                byte
+            },
+        }
+    }
+
+    /// Advances this generator by a fixed amount and returns a new generator
+    /// seeded from it, for the frame-synced RNG. Entropy splits into more
+    /// entropy, and a fixed sequence into the same sequence one byte further
+    /// along each time.
+    pub fn split(&mut self) -> ChipRng {
+        match &self.kind {
+            RngKind::Entropy => ChipRng::new(RngKind::Entropy),
+            RngKind::Xorshift(_) | RngKind::Pcg(_) => {
+                let seed = (0..8).fold(0, |seed, _| seed << 8 | self.next_byte() as u64);
+                ChipRng::new(RngKind::Xorshift(seed))
+            },
+            RngKind::Fixed(_) => {
+                let split = self.clone();
+                self.state += 1;
+                split
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(kind: RngKind) -> Vec<u8> {
+        let mut rng = ChipRng::new(kind);
+        (0..16).map(|_| rng.next_byte()).collect()
+    }
+
+    #[test]
+    fn each_seeded_kind_repeats_itself_and_differs_from_the_others() {
+        let kinds = [RngKind::Xorshift(42), RngKind::Pcg(42), RngKind::Fixed(vec![1, 2, 3])];
+        let sequences: Vec<_> = kinds.iter().map(|kind| bytes(kind.clone())).collect();
+
+        for (kind, sequence) in kinds.iter().zip(&sequences) {
+            assert_eq!(bytes(kind.clone()), *sequence, "{:?}", kind);
+        }
+        assert_ne!(sequences[0], sequences[1]);
+        assert_ne!(sequences[0], sequences[2]);
+        assert_ne!(sequences[1], sequences[2]);
+
+        assert_eq!(sequences[2][..4], [1, 2, 3, 1]);
+        assert_ne!(bytes(RngKind::Xorshift(42)), bytes(RngKind::Xorshift(43)));
+        assert_ne!(bytes(RngKind::Xorshift(0)), [0; 16]);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::emulator::{Display, Memory, Registers, Timers};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("the save state was made with a different ROM loaded")]
+    RomMismatch,
+}
+
+/// Everything a ROM can see of the chip at one point, made with
+/// Chip::save_state and put back with Chip::load_state.
+#[derive(Clone)]
+pub struct SaveState {
+    /// The SHA-1 of the ROM that was loaded, so the state can't be loaded
+    /// into a different game.
+    pub rom_sha1: [u8; 20],
+    pub memory: Memory,
+    pub registers: Registers,
+    pub timers: Timers,
+    pub display: Display,
+    /// Whether Fx0A was waiting for a key press.
+    pub waiting_for_key: bool,
+}
+
+impl SaveState {
+    /// The SHA-1 of a ROM, as kept in rom_sha1.
+    pub fn hash_rom(rom_data: &[u8]) -> [u8; 20] {
+        sha1_smol::Sha1::from(rom_data).digest().bytes()
+    }
+}
@@ -0,0 +1,264 @@
+use crate::emulator::{Chip, Display, Memory, Palette, Resolution, RomSettings, SchipPolicy};
+use std::path::Path;
+
+/// How far one press changes the clock speed.
+const CLOCK_STEP_HZ: u32 = 100;
+
+/// Keypad keys for using the menu, laid out like arrow keys around 5.
+const KEY_UP: u8 = 0x2;
+const KEY_DOWN: u8 = 0x8;
+const KEY_LEFT: u8 = 0x4;
+const KEY_RIGHT: u8 = 0x6;
+const KEY_SELECT: u8 = 0x5;
+
+/// Each row of the menu is a glyph tall, with a pixel between rows.
+const ROW_HEIGHT: u8 = 6;
+const GLYPH_WIDTH: u8 = 5;
+const LEFT_MARGIN: u8 = 4;
+
+/// The settings the menu can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsOption {
+    ClockHz,
+    Palette,
+    SchipCollisionCount,
+    NoCoordWrap,
+    WarnLowJumps,
+    ClampSpriteReads,
+    SchipOpcodes,
+}
+
+impl SettingsOption {
+    /// Every option, in the order they are listed.
+    pub const ALL: [SettingsOption; 7] = [
+        SettingsOption::ClockHz,
+        SettingsOption::Palette,
+        SettingsOption::SchipCollisionCount,
+        SettingsOption::NoCoordWrap,
+        SettingsOption::WarnLowJumps,
+        SettingsOption::ClampSpriteReads,
+        SettingsOption::SchipOpcodes,
+    ];
+
+    /// The option's name, for showing somewhere other than the menu itself,
+    /// as the font only has the hex digits.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettingsOption::ClockHz => "Clock speed",
+            SettingsOption::Palette => "Palette",
+            SettingsOption::SchipCollisionCount => "SCHIP collision count",
+            SettingsOption::NoCoordWrap => "No coordinate wrapping",
+            SettingsOption::WarnLowJumps => "Warn on low jumps",
+            SettingsOption::ClampSpriteReads => "Clamp sprite reads",
+            SettingsOption::SchipOpcodes => "SCHIP opcodes",
+        }
+    }
+}
+
+/// A settings overlay drawn with the font, for changing the clock speed,
+/// palette and quirks while a ROM runs, using the keypad: 2 and 8 move
+/// between options, 4 and 6 change the selected one and 5 toggles it.
+///
+/// The font only has the hex digits, so each row is the option's number
+/// followed by its value: the clock speed in Hz, the index of the palette
+/// preset (F for a custom palette), 0 or 1 for the quirks that are on or
+/// off, and 0, 1 or 2 for erroring on, ignoring or enabling SCHIP opcodes.
+/// SettingsOption::name gives the selected option's name to show elsewhere,
+/// such as the window title.
+pub struct SettingsMenu {
+    open: bool,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        Self { open: false, selected: 0 }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn selected(&self) -> SettingsOption {
+        SettingsOption::ALL[self.selected]
+    }
+
+    /// Handles a keypad key being pressed while the menu is open, changing
+    /// the chip or palette for the selected option. Returns whether a setting
+    /// changed.
+    pub fn press_key(&mut self, key: u8, chip: &mut Chip, palette: &mut Palette) -> bool {
+        let options = SettingsOption::ALL.len();
+        match key {
+            KEY_UP => self.selected = (self.selected + options - 1) % options,
+            KEY_DOWN => self.selected = (self.selected + 1) % options,
+            KEY_LEFT => return Self::change(self.selected(), false, chip, palette),
+            KEY_RIGHT | KEY_SELECT => return Self::change(self.selected(), true, chip, palette),
+            _ => (),
+        }
+        false
+    }
+
+    /// Moves an option to its next value, or its previous one. Toggles are
+    /// flipped either way.
+    fn change(option: SettingsOption, forward: bool, chip: &mut Chip, palette: &mut Palette) -> bool {
+        let mut quirks = chip.quirks();
+        match option {
+            SettingsOption::ClockHz => {
+                let clock_hz = if forward {
+                    chip.clock_hz().saturating_add(CLOCK_STEP_HZ)
+                } else {
+                    chip.clock_hz().saturating_sub(CLOCK_STEP_HZ)
+                };
+                chip.set_clock_hz(clock_hz);
+                return true;
+            }
+            SettingsOption::Palette => {
+                let presets = Palette::PRESETS.len();
+                *palette = match Palette::PRESETS.iter().position(|preset| preset == palette) {
+                    Some(index) if !forward => Palette::PRESETS[(index + presets - 1) % presets],
+                    _ if !forward => Palette::PRESETS[presets - 1],
+                    _ => palette.next_preset(),
+                };
+                return true;
+            }
+            SettingsOption::SchipCollisionCount => quirks.schip_collision_count = !quirks.schip_collision_count,
+            SettingsOption::NoCoordWrap => quirks.no_coord_wrap = !quirks.no_coord_wrap,
+            SettingsOption::WarnLowJumps => quirks.warn_low_jumps = !quirks.warn_low_jumps,
+            SettingsOption::ClampSpriteReads => quirks.clamp_sprite_reads = !quirks.clamp_sprite_reads,
+            SettingsOption::SchipOpcodes => {
+                let policies = [SchipPolicy::Error, SchipPolicy::Ignore, SchipPolicy::Enable];
+                let index = policies.iter().position(|policy| *policy == quirks.schip_opcodes).unwrap_or(0);
+                let step = if forward { 1 } else { policies.len() - 1 };
+                quirks.schip_opcodes = policies[(index + step) % policies.len()];
+            }
+        }
+        chip.set_quirks(quirks);
+        true
+    }
+
+    /// The value of an option as it is shown in the menu.
+    fn value(option: SettingsOption, chip: &Chip, palette: &Palette) -> String {
+        let quirks = chip.quirks();
+        let toggle = |on: bool| if on { "1" } else { "0" }.to_string();
+        match option {
+            SettingsOption::ClockHz => chip.clock_hz().to_string(),
+            SettingsOption::Palette => match Palette::PRESETS.iter().position(|preset| preset == palette) {
+                Some(index) => index.to_string(),
+                None => "F".to_string(),
+            },
+            SettingsOption::SchipCollisionCount => toggle(quirks.schip_collision_count),
+            SettingsOption::NoCoordWrap => toggle(quirks.no_coord_wrap),
+            SettingsOption::WarnLowJumps => toggle(quirks.warn_low_jumps),
+            SettingsOption::ClampSpriteReads => toggle(quirks.clamp_sprite_reads),
+            SettingsOption::SchipOpcodes => match quirks.schip_opcodes {
+                SchipPolicy::Error => "0",
+                SchipPolicy::Ignore => "1",
+                SchipPolicy::Enable => "2",
+            }
+            .to_string(),
+        }
+    }
+
+    /// Draws the menu in high resolution, with a bar beside the selected
+    /// option.
+    pub fn render(&self, chip: &Chip, palette: &Palette) -> Display {
+        let mut display = Display::new(1, palette.background, palette.foreground);
+        display.set_resolution(Resolution::High);
+        let memory = Memory::new();
+
+        for (row, option) in SettingsOption::ALL.iter().enumerate() {
+            let y = 2 + row as u8 * ROW_HEIGHT;
+            if row == self.selected {
+                for bar_y in y..y + 5 {
+                    display.draw_pixel(1, bar_y);
+                }
+            }
+            let text = format!("{:X} {}", row + 1, Self::value(*option, chip, palette));
+            Self::draw_text(&mut display, &memory, LEFT_MARGIN, y, &text);
+        }
+        display
+    }
+
+    /// Draws hex digits with the font, leaving a gap for spaces.
+    fn draw_text(display: &mut Display, memory: &Memory, x: u8, y: u8, text: &str) {
+        for (column, character) in text.chars().enumerate() {
+            if let Some(digit) = character.to_digit(16) {
+                let glyph: Vec<u8> = (0..5).map(|row| memory.read(digit as u16 * 5 + row)).collect();
+                display.draw_sprite(x + column as u8 * GLYPH_WIDTH, y, &glyph);
+            }
+        }
+    }
+
+    /// The chip's current settings, in the same form as a ROM's sidecar file.
+    pub fn settings(chip: &Chip, palette: &Palette) -> RomSettings {
+        RomSettings {
+            quirks: Some(chip.quirks()),
+            clock_hz: Some(chip.clock_hz()),
+            palette: palette.preset_name().map(str::to_string),
+        }
+    }
+
+    /// Saves the chip's current settings to a config file, which can be read
+    /// back with RomSettings::parse.
+    pub fn save<P: AsRef<Path>>(path: P, chip: &Chip, palette: &Palette) -> std::io::Result<()> {
+        let contents = toml::to_string(&Self::settings(chip, palette))
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Quirks;
+
+    #[test]
+    fn the_keypad_moves_between_options_and_wraps_around() {
+        let mut menu = SettingsMenu::new();
+        let mut chip = Chip::headless(Quirks::new());
+        let mut palette = Palette::WHITE;
+        assert_eq!(menu.selected(), SettingsOption::ClockHz);
+
+        assert!(!menu.press_key(KEY_UP, &mut chip, &mut palette));
+        assert_eq!(menu.selected(), SettingsOption::SchipOpcodes);
+        menu.press_key(KEY_DOWN, &mut chip, &mut palette);
+        menu.press_key(KEY_DOWN, &mut chip, &mut palette);
+        assert_eq!(menu.selected(), SettingsOption::Palette);
+
+        // Keys that aren't used by the menu do nothing.
+        assert!(!menu.press_key(0xA, &mut chip, &mut palette));
+        assert_eq!(menu.selected(), SettingsOption::Palette);
+    }
+
+    #[test]
+    fn changing_an_option_updates_the_live_chip() {
+        let mut menu = SettingsMenu::new();
+        let mut chip = Chip::headless(Quirks::new());
+        let mut palette = Palette::WHITE;
+
+        let clock_hz = chip.clock_hz();
+        assert!(menu.press_key(KEY_RIGHT, &mut chip, &mut palette));
+        assert_eq!(chip.clock_hz(), clock_hz + CLOCK_STEP_HZ);
+
+        for _ in 0..3 {
+            menu.press_key(KEY_DOWN, &mut chip, &mut palette);
+        }
+        assert_eq!(menu.selected(), SettingsOption::NoCoordWrap);
+        assert!(menu.press_key(KEY_SELECT, &mut chip, &mut palette));
+        assert!(chip.quirks().no_coord_wrap);
+        assert!(menu.press_key(KEY_LEFT, &mut chip, &mut palette));
+        assert!(!chip.quirks().no_coord_wrap);
+
+        let path = std::env::temp_dir().join(format!("chirp-8-settings-test-{}.toml", std::process::id()));
+        SettingsMenu::save(&path, &chip, &palette).unwrap();
+        let saved = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+        let saved = RomSettings::parse(&saved.unwrap()).unwrap();
+        assert_eq!(saved, SettingsMenu::settings(&chip, &palette));
+        assert_eq!(saved.clock_hz, Some(clock_hz + CLOCK_STEP_HZ));
+    }
+}
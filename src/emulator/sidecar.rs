@@ -0,0 +1,73 @@
+use crate::emulator::Quirks;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Settings for a single ROM, read from a sidecar file next to it with
+/// `.toml` added to its name, e.g. `pong.ch8.toml`:
+///
+/// ```toml
+/// clock_hz = 1000
+/// palette = "amber"
+///
+/// [quirks]
+/// schip_collision_count = true
+/// ```
+///
+/// Quirks that aren't listed keep their default.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RomSettings {
+    // TOML needs the plain values written before the quirks table.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_hz: Option<u32>,
+    /// The name of one of the preset palettes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<Quirks>,
+}
+
+impl RomSettings {
+    /// The sidecar file's path for a ROM. A gzip compressed ROM shares the
+    /// sidecar of the uncompressed one, so `pong.ch8.gz` uses `pong.ch8.toml`.
+    pub fn sidecar_path(rom_path: &Path) -> PathBuf {
+        let rom_path = match rom_path.extension() {
+            Some(extension) if extension == "gz" => rom_path.with_extension(""),
+            _ => rom_path.to_path_buf(),
+        };
+        let mut path = rom_path.into_os_string();
+        path.push(".toml");
+        PathBuf::from(path)
+    }
+
+    /// Reads the sidecar file for a ROM, if it has one.
+    pub fn load_sidecar(rom_path: &Path) -> std::io::Result<Option<RomSettings>> {
+        let path = Self::sidecar_path(rom_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents)
+            .map(Some)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn parse(contents: &str) -> Result<RomSettings, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_is_named_after_the_rom() {
+        assert_eq!(RomSettings::sidecar_path(Path::new("roms/pong.ch8")), PathBuf::from("roms/pong.ch8.toml"));
+    }
+
+    #[test]
+    fn gzip_roms_share_the_uncompressed_sidecar() {
+        assert_eq!(RomSettings::sidecar_path(Path::new("roms/pong.ch8.gz")), PathBuf::from("roms/pong.ch8.toml"));
+    }
+}
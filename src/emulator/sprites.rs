@@ -1,3 +1,11 @@
+/// The 16 font characters are 5 bytes each, starting at address 0.
+pub const FONT_LEN: usize = 16 * 5;
+
+/// Super Chip-48's large font has the digits 0 through 9 at 10 bytes each,
+/// starting right after the small font.
+pub const LARGE_FONT_OFFSET: u16 = FONT_LEN as u16;
+pub const LARGE_FONT_LEN: usize = 10 * 10;
+
 pub fn load_default_sprites(memory: &mut crate::emulator::Memory) {
     // Font Character: 0
     memory.write(0x00, 0b11110000); // ****
@@ -110,4 +118,124 @@ pub fn load_default_sprites(memory: &mut crate::emulator::Memory) {
     memory.write(0x4D, 0b11110000); // ****
     memory.write(0x4E, 0b10000000); // *
     memory.write(0x4F, 0b10000000); // *
+
+    // Large Font Character: 0
+    memory.write(0x50, 0b11111111); // ********
+    memory.write(0x51, 0b11111111); // ********
+    memory.write(0x52, 0b11000011); // **    **
+    memory.write(0x53, 0b11000011); // **    **
+    memory.write(0x54, 0b11000011); // **    **
+    memory.write(0x55, 0b11000011); // **    **
+    memory.write(0x56, 0b11000011); // **    **
+    memory.write(0x57, 0b11000011); // **    **
+    memory.write(0x58, 0b11111111); // ********
+    memory.write(0x59, 0b11111111); // ********
+
+    // Large Font Character: 1
+    memory.write(0x5A, 0b00011000); //    **
+    memory.write(0x5B, 0b01111000); //  ****
+    memory.write(0x5C, 0b01111000); //  ****
+    memory.write(0x5D, 0b00011000); //    **
+    memory.write(0x5E, 0b00011000); //    **
+    memory.write(0x5F, 0b00011000); //    **
+    memory.write(0x60, 0b00011000); //    **
+    memory.write(0x61, 0b00011000); //    **
+    memory.write(0x62, 0b11111111); // ********
+    memory.write(0x63, 0b11111111); // ********
+
+    // Large Font Character: 2
+    memory.write(0x64, 0b11111111); // ********
+    memory.write(0x65, 0b11111111); // ********
+    memory.write(0x66, 0b00000011); //       **
+    memory.write(0x67, 0b00000011); //       **
+    memory.write(0x68, 0b11111111); // ********
+    memory.write(0x69, 0b11111111); // ********
+    memory.write(0x6A, 0b11000000); // **
+    memory.write(0x6B, 0b11000000); // **
+    memory.write(0x6C, 0b11111111); // ********
+    memory.write(0x6D, 0b11111111); // ********
+
+    // Large Font Character: 3
+    memory.write(0x6E, 0b11111111); // ********
+    memory.write(0x6F, 0b11111111); // ********
+    memory.write(0x70, 0b00000011); //       **
+    memory.write(0x71, 0b00000011); //       **
+    memory.write(0x72, 0b11111111); // ********
+    memory.write(0x73, 0b11111111); // ********
+    memory.write(0x74, 0b00000011); //       **
+    memory.write(0x75, 0b00000011); //       **
+    memory.write(0x76, 0b11111111); // ********
+    memory.write(0x77, 0b11111111); // ********
+
+    // Large Font Character: 4
+    memory.write(0x78, 0b11000011); // **    **
+    memory.write(0x79, 0b11000011); // **    **
+    memory.write(0x7A, 0b11000011); // **    **
+    memory.write(0x7B, 0b11000011); // **    **
+    memory.write(0x7C, 0b11111111); // ********
+    memory.write(0x7D, 0b11111111); // ********
+    memory.write(0x7E, 0b00000011); //       **
+    memory.write(0x7F, 0b00000011); //       **
+    memory.write(0x80, 0b00000011); //       **
+    memory.write(0x81, 0b00000011); //       **
+
+    // Large Font Character: 5
+    memory.write(0x82, 0b11111111); // ********
+    memory.write(0x83, 0b11111111); // ********
+    memory.write(0x84, 0b11000000); // **
+    memory.write(0x85, 0b11000000); // **
+    memory.write(0x86, 0b11111111); // ********
+    memory.write(0x87, 0b11111111); // ********
+    memory.write(0x88, 0b00000011); //       **
+    memory.write(0x89, 0b00000011); //       **
+    memory.write(0x8A, 0b11111111); // ********
+    memory.write(0x8B, 0b11111111); // ********
+
+    // Large Font Character: 6
+    memory.write(0x8C, 0b11111111); // ********
+    memory.write(0x8D, 0b11111111); // ********
+    memory.write(0x8E, 0b11000000); // **
+    memory.write(0x8F, 0b11000000); // **
+    memory.write(0x90, 0b11111111); // ********
+    memory.write(0x91, 0b11111111); // ********
+    memory.write(0x92, 0b11000011); // **    **
+    memory.write(0x93, 0b11000011); // **    **
+    memory.write(0x94, 0b11111111); // ********
+    memory.write(0x95, 0b11111111); // ********
+
+    // Large Font Character: 7
+    memory.write(0x96, 0b11111111); // ********
+    memory.write(0x97, 0b11111111); // ********
+    memory.write(0x98, 0b00000011); //       **
+    memory.write(0x99, 0b00000011); //       **
+    memory.write(0x9A, 0b00000110); //      **
+    memory.write(0x9B, 0b00001100); //     **
+    memory.write(0x9C, 0b00011000); //    **
+    memory.write(0x9D, 0b00011000); //    **
+    memory.write(0x9E, 0b00011000); //    **
+    memory.write(0x9F, 0b00011000); //    **
+
+    // Large Font Character: 8
+    memory.write(0xA0, 0b11111111); // ********
+    memory.write(0xA1, 0b11111111); // ********
+    memory.write(0xA2, 0b11000011); // **    **
+    memory.write(0xA3, 0b11000011); // **    **
+    memory.write(0xA4, 0b11111111); // ********
+    memory.write(0xA5, 0b11111111); // ********
+    memory.write(0xA6, 0b11000011); // **    **
+    memory.write(0xA7, 0b11000011); // **    **
+    memory.write(0xA8, 0b11111111); // ********
+    memory.write(0xA9, 0b11111111); // ********
+
+    // Large Font Character: 9
+    memory.write(0xAA, 0b11111111); // ********
+    memory.write(0xAB, 0b11111111); // ********
+    memory.write(0xAC, 0b11000011); // **    **
+    memory.write(0xAD, 0b11000011); // **    **
+    memory.write(0xAE, 0b11111111); // ********
+    memory.write(0xAF, 0b11111111); // ********
+    memory.write(0xB0, 0b00000011); //       **
+    memory.write(0xB1, 0b00000011); //       **
+    memory.write(0xB2, 0b11111111); // ********
+    memory.write(0xB3, 0b11111111); // ********
 }
@@ -0,0 +1,280 @@
+use crate::emulator::{Chip, Display};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+enum Command {
+    SetPressed(u8, bool),
+    Stop,
+}
+
+/// Hands the latest frame from the worker to the render thread without a
+/// lock. The worker swaps each new frame in, dropping the one before if it
+/// was never taken, and the render thread swaps it out, so neither waits on
+/// the other.
+struct FrameSlot {
+    frame: AtomicPtr<(u64, Display)>,
+}
+
+impl FrameSlot {
+    fn new() -> Self {
+        Self { frame: AtomicPtr::new(std::ptr::null_mut()) }
+    }
+
+    fn publish(&self, cycle: u64, frame: Display) {
+        let new = Box::into_raw(Box::new((cycle, frame)));
+        let old = self.frame.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            // Only pointers from Box::into_raw are stored, and swapping one
+            // out leaves this thread as its only owner.
+            drop(unsafe { Box::from_raw(old) });
+        }
+    }
+
+    fn take(&self) -> Option<(u64, Display)> {
+        let frame = self.frame.swap(std::ptr::null_mut(), Ordering::AcqRel);
+        if frame.is_null() {
+            None
+        } else {
+            Some(*unsafe { Box::from_raw(frame) })
+        }
+    }
+}
+
+impl Drop for FrameSlot {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+/// How the render thread has kept up with the worker's frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+    /// How many cycles the worker had run by the latest frame.
+    pub frames_run: u64,
+    /// How many frames made it to the render thread.
+    pub frames_shown: u64,
+    /// How many frames were replaced by a newer one before the render thread
+    /// looked, because it fell behind, e.g. with the clock set too high.
+    pub frames_skipped: u64,
+}
+
+/// Runs a chip on its own thread at 60 cycles a second, so a slow frame on
+/// the render thread doesn't slow down the game. Each cycle's frame is handed
+/// back through a FrameSlot, and input is sent the other way over a channel.
+///
+/// The chip is made on the worker thread, as its sound can't be moved between
+/// threads. Headless runs, like run_and_hash, stay on the calling thread.
+pub struct ThreadedChip {
+    commands: Sender<Command>,
+    frames: Arc<FrameSlot>,
+    latest_frame: Option<Display>,
+    stats: FrameStats,
+    last_frame_rendered: bool,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ThreadedChip {
+    pub fn spawn<F: FnOnce() -> Chip + Send + 'static>(make_chip: F) -> Self {
+        let (commands, command_receiver) = mpsc::channel();
+        let frames = Arc::new(FrameSlot::new());
+        let frame_slot = Arc::clone(&frames);
+
+        let worker = std::thread::spawn(move || {
+            let mut chip = make_chip();
+            let frame_time = Duration::from_secs(1) / 60;
+            let mut next_frame = Instant::now();
+            let mut cycle = 0;
+
+            loop {
+                for command in command_receiver.try_iter() {
+                    match command {
+                        Command::SetPressed(key, pressed) => chip.keyboard_mut().set_pressed(key, pressed),
+                        Command::Stop => return,
+                    }
+                }
+
+                if let Err(err) = chip.cpu_cycle() {
+                    println!("{}", err);
+                }
+                cycle += 1;
+
+                // The render thread has gone, so nobody is watching.
+                if Arc::strong_count(&frame_slot) == 1 {
+                    return;
+                }
+                frame_slot.publish(cycle, chip.latest_frame().clone());
+
+                next_frame += frame_time;
+                if let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        });
+
+        Self {
+            commands,
+            frames,
+            latest_frame: None,
+            stats: FrameStats::default(),
+            last_frame_rendered: true,
+            worker: Some(worker),
+        }
+    }
+
+    /// The most recent frame the worker has finished, or None before the
+    /// first one. Older frames that were never looked at are skipped.
+    pub fn latest_frame(&mut self) -> Option<&Display> {
+        self.last_frame_rendered = true;
+        if let Some((cycle, frame)) = self.frames.take() {
+            let skipped = cycle - self.stats.frames_run - 1;
+            self.stats.frames_run = cycle;
+            self.stats.frames_shown += 1;
+            self.stats.frames_skipped += skipped;
+            self.last_frame_rendered = skipped == 0;
+            self.latest_frame = Some(frame);
+        }
+        self.latest_frame.as_ref()
+    }
+
+    /// How many cycles had run when the latest frame was made.
+    pub fn frames_run(&self) -> u64 {
+        self.stats.frames_run
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// Whether the last call to latest_frame got every frame the worker made
+    /// since the call before, rather than skipping some to catch up. A
+    /// front-end can show this as a performance indicator.
+    pub fn last_frame_rendered(&self) -> bool {
+        self.last_frame_rendered
+    }
+
+    pub fn set_pressed(&self, key: u8, pressed: bool) {
+        let _ = self.commands.send(Command::SetPressed(key, pressed));
+    }
+
+    /// Stops the worker and waits for it to finish its current cycle.
+    pub fn stop(mut self) {
+        self.stop_worker();
+    }
+
+    fn stop_worker(&mut self) {
+        let _ = self.commands.send(Command::Stop);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadedChip {
+    fn drop(&mut self) {
+        self.stop_worker();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Quirks;
+
+    /// Draws a 0 one pixel further right every frame, so every frame's
+    /// display is different.
+    const MOVING_SPRITE: [u8; 8] = [
+        0xA0, 0x00, // LD I, 0
+        0xD0, 0x15, // DRW V0, V1, 5
+        0x70, 0x01, // ADD V0, 1
+        0x12, 0x02, // JP 0x202
+    ];
+    const FRAMES: usize = 30;
+
+    fn chip() -> Chip {
+        let mut chip = Chip::headless(Quirks::new());
+        chip.load_rom(&MOVING_SPRITE).unwrap();
+        chip
+    }
+
+    #[test]
+    fn threaded_frames_match_single_threaded() {
+        let mut single_threaded = chip();
+        let mut expected = vec![single_threaded.latest_frame().pixels_hash()];
+        for _ in 0..FRAMES {
+            single_threaded.run_frame().unwrap();
+            expected.push(single_threaded.latest_frame().pixels_hash());
+        }
+
+        let mut threaded = ThreadedChip::spawn(chip);
+        let mut frames_seen = 0;
+        while threaded.frames_run() < FRAMES as u64 {
+            if let Some(frame) = threaded.latest_frame() {
+                let hash = frame.pixels_hash();
+                let cycle = threaded.frames_run() as usize;
+                if cycle <= FRAMES {
+                    assert_eq!(hash, expected[cycle], "frame {} doesn't match", cycle);
+                    frames_seen += 1;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        threaded.stop();
+
+        assert!(frames_seen > 0);
+    }
+
+    #[test]
+    fn the_slot_keeps_only_the_latest_frame() {
+        let slot = FrameSlot::new();
+        assert!(slot.take().is_none());
+
+        let mut display = Display::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+        slot.publish(1, display.clone());
+        display.draw_pixel(0, 0);
+        slot.publish(2, display.clone());
+
+        let (cycle, frame) = slot.take().unwrap();
+        assert_eq!(cycle, 2);
+        assert_eq!(frame.pixels_hash(), display.pixels_hash());
+        assert!(slot.take().is_none());
+    }
+
+    /// A ThreadedChip whose frames are published by the test rather than a
+    /// worker, standing in for one whose render thread takes its time.
+    fn without_worker() -> ThreadedChip {
+        ThreadedChip {
+            commands: mpsc::channel().0,
+            frames: Arc::new(FrameSlot::new()),
+            latest_frame: None,
+            stats: FrameStats::default(),
+            last_frame_rendered: true,
+            worker: None,
+        }
+    }
+
+    #[test]
+    fn frames_the_render_thread_is_too_slow_for_are_counted_as_skipped() {
+        let mut threaded = without_worker();
+        let display = Display::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+
+        threaded.frames.publish(1, display.clone());
+        assert!(threaded.latest_frame().is_some());
+        assert!(threaded.last_frame_rendered());
+
+        // The render thread only looks again after three more frames.
+        for cycle in 2..=4 {
+            threaded.frames.publish(cycle, display.clone());
+        }
+        threaded.latest_frame();
+        assert!(!threaded.last_frame_rendered());
+        assert_eq!(threaded.frame_stats(), FrameStats { frames_run: 4, frames_shown: 2, frames_skipped: 2 });
+
+        threaded.frames.publish(5, display);
+        threaded.latest_frame();
+        assert!(threaded.last_frame_rendered());
+        assert_eq!(threaded.frame_stats(), FrameStats { frames_run: 5, frames_shown: 3, frames_skipped: 2 });
+    }
+}
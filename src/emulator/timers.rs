@@ -11,6 +11,7 @@
 //
 // The sound produced by the Chip-8 interpreter has only one tone. The
 // frequency of this tone is decided by the author of the interpreter.
+#[derive(Clone)]
 pub struct Timers {
     delay: u8,
     sound: u8,
@@ -2,44 +2,59 @@
 mod emulator;
 mod render;
 
-use crate::emulator::Chip;
-use crate::render::SurfaceBuilder;
+use crate::emulator::{Chip, ChipEvent, FramePacer, Palette, Quirks, QuirksTestPlatform, RomSettings, SettingsMenu};
+use crate::render::{Surface, SurfaceBuilder, SurfaceError};
 use glutin::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
 };
-use std::time::SystemTime;
+use std::time::Instant;
 
-const FRAME_RATE: f64 = 60.0;
+const FRAME_RATE: u32 = 60;
+const TITLE: &str = "CHIRP-8 Emulator";
+/// Where the settings menu saves its changes, in the same form as a ROM's
+/// sidecar file.
+const CONFIG_PATH: &str = "chirp-8.toml";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    // The quirks test only prints its results, so it runs before there is a
+    // window.
+    if let Some(index) = args.iter().position(|arg| arg == "--quirks-test") {
+        match args.get(index + 1) {
+            Some(path) => {
+                let rom = std::fs::read(path)?;
+                match emulator::run_quirks_test(&rom, Quirks::new(), QuirksTestPlatform::Chip8) {
+                    Some(result) => println!("{:#?}", result),
+                    None => println!("Couldn't read the results of the quirks test."),
+                }
+                return Ok(());
+            }
+            None => println!("--quirks-test requires the path to the quirks test ROM."),
+        }
+    }
+
+    #[cfg(feature = "threaded")]
+    if args.iter().any(|arg| arg == "--threaded") {
+        return run_threaded(args);
+    }
+
+    let mut chip8 = load_chip(&args)?;
+
     let event_loop = EventLoop::new();
+    let mut surface = build_surface(&event_loop)?;
 
-    let mut surface = SurfaceBuilder::new()
-        .with_title("CHIRP-8 Emulator")
-        .with_size(640, 320)
-        .build(&event_loop)?;
+    #[cfg(feature = "gilrs")]
+    let mut gilrs = gilrs::Gilrs::new().ok();
+    #[cfg(feature = "gilrs")]
+    let gamepad_bindings = emulator::GamepadBindings::new();
 
-    let mut chip8 = Chip::new();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/BC_test.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/test_opcode.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/IBM_Logo.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/Fishie_[Hap,_2005].ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/SQRT_Test_[Sergey_Naydenov,_2010].ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/Trip8_Demo_(2008)_[Revival_Studios].ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/Tetris [Fran Dachille, 1991].ch8")).unwrap();
-    chip8.load_rom_from_path(std::path::Path::new("./roms/Pong (1 player).ch8")).unwrap();
-
-    // Test Suite from https://github.com/Timendus/chip8-test-suite.
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/1-chip8-logo.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/2-ibm-logo.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/3-corax+.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/4-flags.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/5-quirks.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/6-keypad.ch8")).unwrap();
-    // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/7-beep.ch8")).unwrap();
-
-    let mut last_cycle = SystemTime::now();
+    let mut palette = chip8.rom_palette().unwrap_or(Palette::WHITE);
+    surface.set_palette(palette);
+    let mut pacer = FramePacer::new(FRAME_RATE);
+    let mut last_poll = Instant::now();
+    let mut settings_menu = SettingsMenu::new();
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -49,18 +64,212 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(size) => surface.resize(size.width, size.height),
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput { input, .. } => chip8.process_input(input),
+                WindowEvent::KeyboardInput { input, .. } => {
+                    // F1 opens and closes the settings menu, saving any
+                    // changes when it closes.
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::F1)
+                    {
+                        settings_menu.toggle();
+                        if settings_menu.is_open() {
+                            surface.window().set_title(&format!("{} - {}", TITLE, settings_menu.selected().name()));
+                        } else {
+                            surface.window().set_title(TITLE);
+                            if let Err(err) = SettingsMenu::save(CONFIG_PATH, &chip8, &palette) {
+                                println!("Failed to save {}: {}", CONFIG_PATH, err);
+                            }
+                        }
+                    }
+                    if settings_menu.is_open() {
+                        let key = input.virtual_keycode.and_then(|keycode| chip8.keyboard().keypad_key(keycode));
+                        if let (ElementState::Pressed, Some(key)) = (input.state, key) {
+                            if settings_menu.press_key(key, &mut chip8, &mut palette) {
+                                surface.set_palette(palette);
+                            }
+                            surface.window().set_title(&format!("{} - {}", TITLE, settings_menu.selected().name()));
+                        }
+                        return;
+                    }
+                    // P cycles through the preset color palettes.
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::P)
+                    {
+                        palette = palette.next_preset();
+                        surface.set_palette(palette);
+                    }
+                    // F5 resumes after a breakpoint.
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::F5)
+                    {
+                        chip8.set_paused(false);
+                    }
+                    chip8.process_input(input)
+                }
                 _ => (),
             },
             Event::MainEventsCleared => {
-                if let Ok(elapsed) = last_cycle.elapsed() {
-                    if elapsed.as_secs_f64() > 1.0 / FRAME_RATE {
-                        chip8.cpu_cycle();
+                #[cfg(feature = "gilrs")]
+                if let Some(gilrs) = gilrs.as_mut() {
+                    while let Some(event) = gilrs.next_event() {
+                        gamepad_bindings.process_event(event.event, chip8.keyboard_mut());
+                    }
+                }
+
+                let now = Instant::now();
+                let elapsed = now - last_poll;
+                last_poll = now;
+
+                // The ROM is held while the settings menu is open.
+                if settings_menu.is_open() {
+                    surface.update_with_display(&settings_menu.render(&chip8, &palette));
+                    surface.request_redraw();
+                    return;
+                }
+
+                for _ in 0..pacer.advance(elapsed) {
+                    if let Err(err) = chip8.cpu_cycle() {
+                        println!("{}", err);
+                    }
+                    while let Some(event) = chip8.poll_event() {
+                        if let ChipEvent::BreakpointHit(address) = event {
+                            println!("Paused at the breakpoint at {:#05x}. Press F5 to resume.", address);
+                        }
+                    }
+                }
+                surface.set_waiting_for_key(chip8.waiting_for_key());
+                surface.set_sound_active(chip8.sound_active());
+                surface.update_with_display(chip8.latest_frame());
+                surface.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                surface.update().unwrap();
+                surface.render();
+            }
+            _ => (),
+        }
+    });
+}
 
-                        last_cycle = SystemTime::now();
+/// Makes the chip, with the config file's settings, and loads the ROM and
+/// anything else asked for on the command line.
+fn load_chip(args: &[String]) -> Result<Chip, Box<dyn std::error::Error>> {
+    let mut chip8 = Chip::new();
+    // Applied before the ROM is loaded, so its sidecar file takes priority.
+    if let Ok(contents) = std::fs::read_to_string(CONFIG_PATH) {
+        match RomSettings::parse(&contents) {
+            Ok(settings) => chip8.apply_settings(&settings),
+            Err(err) => println!("Failed to read {}: {}", CONFIG_PATH, err),
+        }
+    }
+
+    // --rom - reads the ROM from stdin, e.g. `cat game.ch8 | chirp-8 --rom -`.
+    // Without --rom, a default ROM is loaded.
+    match args.iter().position(|arg| arg == "--rom").map(|index| args.get(index + 1).map(String::as_str)) {
+        Some(Some("-")) => chip8.load_rom_from_reader(std::io::stdin().lock())?,
+        Some(Some(path)) => chip8.load_rom_from_path(std::path::Path::new(path))?,
+        Some(None) => println!("--rom requires a file path, or - to read from stdin."),
+        None => {
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/BC_test.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/test_opcode.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/IBM_Logo.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/Fishie_[Hap,_2005].ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/SQRT_Test_[Sergey_Naydenov,_2010].ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/Trip8_Demo_(2008)_[Revival_Studios].ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/Tetris [Fran Dachille, 1991].ch8")).unwrap();
+            chip8.load_rom_from_path(std::path::Path::new("./roms/Pong (1 player).ch8"))?;
+
+            // Test Suite from https://github.com/Timendus/chip8-test-suite.
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/1-chip8-logo.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/2-ibm-logo.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/3-corax+.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/4-flags.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/5-quirks.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/6-keypad.ch8")).unwrap();
+            // chip8.load_rom_from_path(std::path::Path::new("./roms/tests/7-beep.ch8")).unwrap();
+        },
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--disasm-out") {
+        match args.get(index + 1) {
+            Some(path) => chip8.export_disassembly(path)?,
+            None => println!("--disasm-out requires a file path."),
+        }
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--break") {
+        match args.get(index + 1).and_then(|address| u16::from_str_radix(address.trim_start_matches("0x"), 16).ok()) {
+            Some(address) => chip8.add_breakpoint(address),
+            None => println!("--break requires a hexadecimal address."),
+        }
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--input-script") {
+        match args.get(index + 1) {
+            Some(path) => chip8.load_input_script(path)?,
+            None => println!("--input-script requires a file path."),
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--selftest") {
+        chip8.self_test();
+    }
+
+    Ok(chip8)
+}
+
+fn build_surface<T>(event_loop: &EventLoop<T>) -> Result<Surface, SurfaceError> {
+    SurfaceBuilder::new()
+        .with_title(TITLE)
+        .with_size(640, 320)
+        .with_wait_indicator(true)
+        .build(event_loop)
+}
+
+/// With --threaded, the chip runs on its own thread in a ThreadedChip, and
+/// only keys are sent to it. The settings menu, breakpoints and the rest of
+/// the chip's controls need it on this thread, so they are left out.
+#[cfg(feature = "threaded")]
+fn run_threaded(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let event_loop = EventLoop::new();
+    let mut surface = build_surface(&event_loop)?;
+    let keyboard = emulator::Keyboard::new();
+    let mut palette = Palette::WHITE;
+    surface.set_palette(palette);
+
+    // The chip's sound can't move between threads, so it is made on the
+    // worker, which can only print its errors.
+    let mut chip8 = emulator::ThreadedChip::spawn(move || {
+        load_chip(&args).unwrap_or_else(|err| {
+            println!("{}", err);
+            Chip::new()
+        })
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(size) => surface.resize(size.width, size.height),
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput { input, .. } => {
+                    // P cycles through the preset color palettes.
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::P)
+                    {
+                        palette = palette.next_preset();
+                        surface.set_palette(palette);
+                    }
+                    if let Some(key) = input.virtual_keycode.and_then(|keycode| keyboard.keypad_key(keycode)) {
+                        chip8.set_pressed(key, input.state == ElementState::Pressed);
                     }
                 }
-                surface.update_with_display(chip8.display());
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                if let Some(frame) = chip8.latest_frame() {
+                    surface.update_with_display(frame);
+                }
                 surface.request_redraw();
             }
             Event::RedrawRequested(_) => {
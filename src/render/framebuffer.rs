@@ -0,0 +1,58 @@
+use crate::render::{gl, Texture};
+
+/// An offscreen framebuffer drawn into a texture, so what is drawn can be
+/// sampled by a later pass.
+#[derive(Debug)]
+pub struct Framebuffer {
+    texture: Texture,
+
+    framebuffer_id: gl::FramebufferId,
+
+    gl: gl::Gl,
+}
+
+impl Framebuffer {
+    pub fn new(gl: &gl::Gl, width: usize, height: usize) -> Self {
+        let mut texture = Texture::new(gl);
+        texture.allocate_rgba(width, height);
+
+        let framebuffer = Framebuffer {
+            texture,
+            framebuffer_id: gl.create_framebuffer(),
+            gl: gl.clone(),
+        };
+
+        framebuffer.bind();
+        gl.attach_texture_to_framebuffer(framebuffer.texture.id());
+        framebuffer.unbind();
+
+        framebuffer
+    }
+
+    /// Reallocates the texture, losing what was drawn into it, if the size
+    /// has changed.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if (self.texture.width(), self.texture.height()) != (width, height) {
+            self.texture.allocate_rgba(width, height);
+        }
+    }
+
+    /// Draws into the texture rather than the window until unbound.
+    pub fn bind(&self) {
+        self.gl.bind_framebuffer(&self.framebuffer_id);
+    }
+
+    pub fn unbind(&self) {
+        self.gl.unbind_framebuffer();
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.gl.delete_framebuffer(&self.framebuffer_id);
+    }
+}
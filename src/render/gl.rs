@@ -4,6 +4,9 @@ use std::ffi::CString;
 use std::rc::Rc;
 use thiserror::Error;
 
+#[cfg(test)]
+pub mod recording;
+
 #[derive(Debug, Error)]
 pub enum GlError {
     #[error("OpenGL failed to create a new shader object")]
@@ -76,10 +79,10 @@ impl Gl {
     }
 
     #[inline]
-    pub fn set_view_port(&self, x: u32, y: u32, width: u32, height: u32) {
+    pub fn set_view_port(&self, x: i32, y: i32, width: u32, height: u32) {
         unsafe {
             self.gl
-                .Viewport(x as i32, y as i32, width as i32, height as i32)
+                .Viewport(x, y, width as i32, height as i32)
         };
     }
 }
@@ -302,6 +305,12 @@ impl UploadableUniform for [u32; 64] {
     }
 }
 
+impl UploadableUniform for [u32; 256] {
+    fn upload(&self, gl: &Gl, uniform_location: &UniformLocationId) {
+        unsafe { gl.gl.Uniform1uiv(uniform_location.id, 256, self.as_ptr()) }
+    }
+}
+
 #[derive(Debug)]
 pub struct VertexArrayId {
     id: u32,
@@ -421,6 +430,132 @@ impl Gl {
     }
 }
 
+#[derive(Debug)]
+pub struct TextureId {
+    id: u32,
+}
+
+impl Gl {
+    #[inline]
+    pub fn create_texture(&self) -> TextureId {
+        let mut id: u32 = 0;
+        unsafe { self.gl.GenTextures(1, &mut id) };
+        TextureId { id }
+    }
+
+    #[inline]
+    pub fn delete_texture(&self, texture: &TextureId) {
+        unsafe { self.gl.DeleteTextures(1, [texture.id].as_ptr()) };
+    }
+
+    #[inline]
+    pub fn bind_texture(&self, texture: &TextureId) {
+        unsafe { self.gl.BindTexture(bindings::TEXTURE_2D, texture.id) };
+    }
+
+    #[inline]
+    pub fn unbind_texture(&self) {
+        unsafe { self.gl.BindTexture(bindings::TEXTURE_2D, 0) };
+    }
+
+    /// Sets the bound texture to be sampled without smoothing, so scaled up
+    /// pixels keep their hard edges.
+    #[inline]
+    pub fn set_texture_nearest_filter(&self) {
+        unsafe {
+            self.gl.TexParameteri(bindings::TEXTURE_2D, bindings::TEXTURE_MIN_FILTER, bindings::NEAREST as i32);
+            self.gl.TexParameteri(bindings::TEXTURE_2D, bindings::TEXTURE_MAG_FILTER, bindings::NEAREST as i32);
+            self.gl.TexParameteri(bindings::TEXTURE_2D, bindings::TEXTURE_WRAP_S, bindings::CLAMP_TO_EDGE as i32);
+            self.gl.TexParameteri(bindings::TEXTURE_2D, bindings::TEXTURE_WRAP_T, bindings::CLAMP_TO_EDGE as i32);
+        }
+    }
+
+    /// Replaces the bound texture's image with 8-bit RGBA pixels, row by row
+    /// from the top left.
+    #[inline]
+    pub fn texture_image_rgba(&self, width: usize, height: usize, data: &[u8]) {
+        debug_assert_eq!(data.len(), width * height * 4);
+        unsafe {
+            self.gl.TexImage2D(
+                bindings::TEXTURE_2D,
+                0,
+                bindings::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                bindings::RGBA,
+                bindings::UNSIGNED_BYTE,
+                data.as_ptr() as *const bindings::types::GLvoid,
+            )
+        };
+    }
+}
+
+impl Gl {
+    /// Gives the bound texture room for an 8-bit RGBA image, leaving its
+    /// pixels undefined, e.g. to be drawn into through a framebuffer.
+    #[inline]
+    pub fn texture_image_empty_rgba(&self, width: usize, height: usize) {
+        unsafe {
+            self.gl.TexImage2D(
+                bindings::TEXTURE_2D,
+                0,
+                bindings::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                bindings::RGBA,
+                bindings::UNSIGNED_BYTE,
+                std::ptr::null(),
+            )
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct FramebufferId {
+    id: u32,
+}
+
+impl Gl {
+    #[inline]
+    pub fn create_framebuffer(&self) -> FramebufferId {
+        let mut id: u32 = 0;
+        unsafe { self.gl.GenFramebuffers(1, &mut id) };
+        FramebufferId { id }
+    }
+
+    #[inline]
+    pub fn delete_framebuffer(&self, framebuffer: &FramebufferId) {
+        unsafe { self.gl.DeleteFramebuffers(1, [framebuffer.id].as_ptr()) };
+    }
+
+    #[inline]
+    pub fn bind_framebuffer(&self, framebuffer: &FramebufferId) {
+        unsafe { self.gl.BindFramebuffer(bindings::FRAMEBUFFER, framebuffer.id) };
+    }
+
+    /// Binds the window's framebuffer again.
+    #[inline]
+    pub fn unbind_framebuffer(&self) {
+        unsafe { self.gl.BindFramebuffer(bindings::FRAMEBUFFER, 0) };
+    }
+
+    /// Makes the texture the bound framebuffer's color buffer.
+    #[inline]
+    pub fn attach_texture_to_framebuffer(&self, texture: &TextureId) {
+        unsafe {
+            self.gl.FramebufferTexture2D(
+                bindings::FRAMEBUFFER,
+                bindings::COLOR_ATTACHMENT0,
+                bindings::TEXTURE_2D,
+                texture.id,
+                0,
+            )
+        };
+    }
+}
+
 impl Gl {
     #[inline]
     pub fn draw_elements(&self, indice_count: usize) {
@@ -0,0 +1,280 @@
+//! A stand-in for OpenGL in tests, which records the calls that matter to
+//! what ends up on screen instead of drawing anything, so rendering can be
+//! checked without a window or a GPU. Every test thread has its own
+//! recording.
+
+use crate::render::bindings::{self, types::*};
+use crate::render::gl::Gl;
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr};
+
+/// A call made through the recording Gl. Uniforms are named rather than
+/// given by location.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    ClearColor(f32, f32, f32, f32),
+    Clear(u32),
+    Viewport(i32, i32, i32, i32),
+    ShaderSource(String),
+    UseProgram(u32),
+    Uniform(String, Vec<f32>),
+    UniformUints(String, Vec<u32>),
+    BindVertexArray(u32),
+    DrawElements(i32),
+    BindTexture(u32),
+    /// The RGBA pixels uploaded, or None when only space was allocated.
+    TexImage2D(i32, i32, Option<Vec<u8>>),
+    /// 0 for the window's framebuffer.
+    BindFramebuffer(u32),
+    /// The texture attached as the bound framebuffer's color buffer.
+    FramebufferTexture2D(u32),
+}
+
+#[derive(Default)]
+struct Recording {
+    calls: Vec<Call>,
+    /// The names of the uniforms looked up, by location.
+    uniforms: Vec<String>,
+    last_id: u32,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Recording> = RefCell::new(Recording::default());
+}
+
+fn record(call: Call) {
+    RECORDING.with(|recording| recording.borrow_mut().calls.push(call));
+}
+
+fn next_id() -> u32 {
+    RECORDING.with(|recording| {
+        let mut recording = recording.borrow_mut();
+        recording.last_id += 1;
+        recording.last_id
+    })
+}
+
+fn uniform_name(location: GLint) -> String {
+    RECORDING.with(|recording| recording.borrow().uniforms[location as usize].clone())
+}
+
+/// A Gl that records into this thread's recording, which is started afresh.
+pub fn gl() -> Gl {
+    RECORDING.with(|recording| *recording.borrow_mut() = Recording::default());
+    Gl::load_with(load)
+}
+
+/// Every call recorded so far.
+pub fn calls() -> Vec<Call> {
+    RECORDING.with(|recording| recording.borrow().calls.clone())
+}
+
+/// Forgets the calls recorded so far, e.g. those made while setting up.
+pub fn clear_calls() {
+    RECORDING.with(|recording| recording.borrow_mut().calls.clear());
+}
+
+/// The values uploaded to a float uniform, oldest first.
+pub fn uniform_uploads(name: &str) -> Vec<Vec<f32>> {
+    calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            Call::Uniform(uniform, values) if uniform == name => Some(values),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The values uploaded to an unsigned integer array uniform, oldest first.
+pub fn uint_uniform_uploads(name: &str) -> Vec<Vec<u32>> {
+    calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            Call::UniformUints(uniform, values) if uniform == name => Some(values),
+            _ => None,
+        })
+        .collect()
+}
+
+fn load(name: &'static str) -> *const c_void {
+    match name {
+        "glGetError" => get_error as *const c_void,
+        "glClearColor" => clear_color as *const c_void,
+        "glClear" => clear as *const c_void,
+        "glViewport" => viewport as *const c_void,
+        "glCreateShader" => create_shader as *const c_void,
+        "glDeleteShader" => ignore_id as *const c_void,
+        "glShaderSource" => shader_source as *const c_void,
+        "glCompileShader" => ignore_id as *const c_void,
+        "glGetShaderiv" => get_status as *const c_void,
+        "glGetProgramiv" => get_status as *const c_void,
+        "glCreateProgram" => create_program as *const c_void,
+        "glDeleteProgram" => ignore_id as *const c_void,
+        "glAttachShader" => ignore_ids as *const c_void,
+        "glDetachShader" => ignore_ids as *const c_void,
+        "glLinkProgram" => ignore_id as *const c_void,
+        "glUseProgram" => use_program as *const c_void,
+        "glGetUniformLocation" => get_uniform_location as *const c_void,
+        "glUniform1f" => uniform_1f as *const c_void,
+        "glUniform2f" => uniform_2f as *const c_void,
+        "glUniform3f" => uniform_3f as *const c_void,
+        "glUniform1uiv" => uniform_1uiv as *const c_void,
+        "glGenVertexArrays" => gen_ids as *const c_void,
+        "glDeleteVertexArrays" => delete_ids as *const c_void,
+        "glBindVertexArray" => bind_vertex_array as *const c_void,
+        "glVertexAttribPointer" => vertex_attrib_pointer as *const c_void,
+        "glEnableVertexAttribArray" => ignore_id as *const c_void,
+        "glDisableVertexAttribArray" => ignore_id as *const c_void,
+        "glGenBuffers" => gen_ids as *const c_void,
+        "glDeleteBuffers" => delete_ids as *const c_void,
+        "glBindBuffer" => ignore_ids as *const c_void,
+        "glBufferData" => buffer_data as *const c_void,
+        "glGenTextures" => gen_ids as *const c_void,
+        "glDeleteTextures" => delete_ids as *const c_void,
+        "glBindTexture" => bind_texture as *const c_void,
+        "glTexParameteri" => tex_parameter_i as *const c_void,
+        "glTexImage2D" => tex_image_2d as *const c_void,
+        "glDrawElements" => draw_elements as *const c_void,
+        "glGenFramebuffers" => gen_ids as *const c_void,
+        "glDeleteFramebuffers" => delete_ids as *const c_void,
+        "glBindFramebuffer" => bind_framebuffer as *const c_void,
+        "glFramebufferTexture2D" => framebuffer_texture_2d as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+extern "system" fn get_error() -> GLenum {
+    bindings::NO_ERROR
+}
+
+extern "system" fn clear_color(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+    record(Call::ClearColor(red, green, blue, alpha));
+}
+
+extern "system" fn clear(mask: GLbitfield) {
+    record(Call::Clear(mask));
+}
+
+extern "system" fn viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+    record(Call::Viewport(x, y, width, height));
+}
+
+extern "system" fn create_shader(_shader_type: GLenum) -> GLuint {
+    next_id()
+}
+
+extern "system" fn create_program() -> GLuint {
+    next_id()
+}
+
+extern "system" fn ignore_id(_id: GLuint) {}
+
+extern "system" fn ignore_ids(_first: GLuint, _second: GLuint) {}
+
+extern "system" fn shader_source(_shader: GLuint, count: GLsizei, sources: *const *const GLchar, _lengths: *const GLint) {
+    for index in 0..count as usize {
+        let source = unsafe { CStr::from_ptr(*sources.add(index)) };
+        record(Call::ShaderSource(source.to_string_lossy().into_owned()));
+    }
+}
+
+/// Every shader compiles and every program links.
+extern "system" fn get_status(_id: GLuint, _name: GLenum, value: *mut GLint) {
+    unsafe { *value = bindings::TRUE as GLint };
+}
+
+extern "system" fn use_program(program: GLuint) {
+    record(Call::UseProgram(program));
+}
+
+extern "system" fn get_uniform_location(_program: GLuint, name: *const GLchar) -> GLint {
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+    RECORDING.with(|recording| {
+        let mut recording = recording.borrow_mut();
+        recording.uniforms.push(name);
+        recording.uniforms.len() as GLint - 1
+    })
+}
+
+extern "system" fn uniform_1f(location: GLint, x: GLfloat) {
+    record(Call::Uniform(uniform_name(location), vec![x]));
+}
+
+extern "system" fn uniform_2f(location: GLint, x: GLfloat, y: GLfloat) {
+    record(Call::Uniform(uniform_name(location), vec![x, y]));
+}
+
+extern "system" fn uniform_3f(location: GLint, x: GLfloat, y: GLfloat, z: GLfloat) {
+    record(Call::Uniform(uniform_name(location), vec![x, y, z]));
+}
+
+extern "system" fn uniform_1uiv(location: GLint, count: GLsizei, values: *const GLuint) {
+    let values = unsafe { std::slice::from_raw_parts(values, count as usize) };
+    record(Call::UniformUints(uniform_name(location), values.to_vec()));
+}
+
+extern "system" fn gen_ids(count: GLsizei, ids: *mut GLuint) {
+    for index in 0..count as usize {
+        unsafe { *ids.add(index) = next_id() };
+    }
+}
+
+extern "system" fn delete_ids(_count: GLsizei, _ids: *const GLuint) {}
+
+extern "system" fn bind_vertex_array(vertex_array: GLuint) {
+    record(Call::BindVertexArray(vertex_array));
+}
+
+extern "system" fn vertex_attrib_pointer(
+    _index: GLuint,
+    _size: GLint,
+    _value_type: GLenum,
+    _normalized: GLboolean,
+    _stride: GLsizei,
+    _offset: *const c_void,
+) {
+}
+
+extern "system" fn buffer_data(_target: GLenum, _size: GLsizeiptr, _data: *const c_void, _usage: GLenum) {}
+
+extern "system" fn bind_texture(_target: GLenum, texture: GLuint) {
+    record(Call::BindTexture(texture));
+}
+
+extern "system" fn tex_parameter_i(_target: GLenum, _name: GLenum, _value: GLint) {}
+
+/// Only 8-bit RGBA images are uploaded.
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_2d(
+    _target: GLenum,
+    _level: GLint,
+    _internal_format: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    _border: GLint,
+    _format: GLenum,
+    _value_type: GLenum,
+    pixels: *const c_void,
+) {
+    let pixels = (!pixels.is_null())
+        .then(|| unsafe { std::slice::from_raw_parts(pixels as *const u8, (width * height * 4) as usize) }.to_vec());
+    record(Call::TexImage2D(width, height, pixels));
+}
+
+extern "system" fn draw_elements(_mode: GLenum, count: GLsizei, _index_type: GLenum, _indices: *const c_void) {
+    record(Call::DrawElements(count));
+}
+
+extern "system" fn bind_framebuffer(_target: GLenum, framebuffer: GLuint) {
+    record(Call::BindFramebuffer(framebuffer));
+}
+
+extern "system" fn framebuffer_texture_2d(
+    _target: GLenum,
+    _attachment: GLenum,
+    _texture_target: GLenum,
+    texture: GLuint,
+    _level: GLint,
+) {
+    record(Call::FramebufferTexture2D(texture));
+}
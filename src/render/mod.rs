@@ -13,3 +13,9 @@ pub use shader::*;
 
 mod vertex_array;
 pub use vertex_array::*;
+
+mod texture;
+pub use texture::*;
+
+mod framebuffer;
+pub use framebuffer::*;
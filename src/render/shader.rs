@@ -19,6 +19,8 @@ pub enum ShaderError {
 pub struct ShaderProgram {
     program_id: gl::ProgramId,
     uniform_locations: HashMap<String, gl::UniformLocationId>,
+    vertex_source: String,
+    fragment_source: String,
     gl: gl::Gl,
 }
 
@@ -31,14 +33,16 @@ impl ShaderProgram {
             gl::ShaderType::Fragment.value(),
         ))?;
 
-        let vertex = Self::compile_shader(gl, gl::ShaderType::Vertex, vertex_source)?;
-        let fragment = Self::compile_shader(gl, gl::ShaderType::Fragment, fragment_source)?;
+        let vertex = Self::compile_shader(gl, gl::ShaderType::Vertex, &vertex_source)?;
+        let fragment = Self::compile_shader(gl, gl::ShaderType::Fragment, &fragment_source)?;
 
         let program = Self::link_shaders(gl, &[vertex, fragment])?;
 
         Ok(Self {
             program_id: program,
             uniform_locations: HashMap::new(),
+            vertex_source,
+            fragment_source,
             gl: gl.clone(),
         })
     }
@@ -46,11 +50,11 @@ impl ShaderProgram {
     fn compile_shader(
         gl: &gl::Gl,
         shader_type: gl::ShaderType,
-        source: String,
+        source: &str,
     ) -> Result<gl::ShaderId, ShaderError> {
         let id = gl.create_shader(shader_type)?;
 
-        gl.set_shader_source(&id, &source)?;
+        gl.set_shader_source(&id, source)?;
         gl.compile_shader(&id)?;
 
         Ok(id)
@@ -73,6 +77,16 @@ impl ShaderProgram {
         Ok(program)
     }
 
+    /// The source the vertex shader was compiled from.
+    pub fn vertex_source(&self) -> &str {
+        &self.vertex_source
+    }
+
+    /// The source the fragment shader was compiled from.
+    pub fn fragment_source(&self) -> &str {
+        &self.fragment_source
+    }
+
     pub fn bind(&self) {
         self.gl.bind_program(&self.program_id);
     }
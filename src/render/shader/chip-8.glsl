@@ -12,17 +12,45 @@ void main() {
 
 #type fragment
 #version 330 core
-uniform uint[64] uPixels;
+// Two entries per column of the 128x64 grid, the top half then the bottom
+// half, with the most significant bit being the top row.
+uniform uint[256] uPixels;
+// The width and height of the display in its current resolution.
+uniform vec2 uGridSize;
+uniform float uWaitIndicator;
+uniform vec3 uBackground;
+uniform vec3 uForeground;
+uniform float uGrayscale;
+uniform float uContrast;
 
 in vec2 vPixelPos;
 
 out vec4 fColor;
 
-int WIDTH = 64;
-int HEIGHT = 32;
-
 void main() {
-    float is_bright = (uPixels[int(vPixelPos.x * WIDTH)] << int((vPixelPos.y * HEIGHT))) >> HEIGHT - 1;
-
-    fColor = vec4(is_bright, is_bright, is_bright, 1.0);
+    int width = int(uGridSize.x);
+    int height = int(uGridSize.y);
+    int x = int(vPixelPos.x * width);
+    int y = int(vPixelPos.y * height);
+    float is_bright = float((uPixels[x * 2 + y / 32] << (y % 32)) >> 31);
+
+    // Blink the bottom right pixel while waiting for a key press.
+    if (uWaitIndicator > 0.5 && x == width - 1 && y == height - 1) {
+        is_bright = 1.0 - is_bright;
+    }
+
+    // Contrast pushes the two colors apart, or pulls them together, around
+    // the color halfway between them.
+    vec3 middle = (uBackground + uForeground) / 2.0;
+    vec3 background = clamp(middle + (uBackground - middle) * uContrast, 0.0, 1.0);
+    vec3 foreground = clamp(middle + (uForeground - middle) * uContrast, 0.0, 1.0);
+
+    vec3 color = mix(background, foreground, is_bright);
+
+    // For grayscale screens, draw the luminance of the colors instead.
+    if (uGrayscale > 0.5) {
+        color = vec3(dot(color, vec3(0.2126, 0.7152, 0.0722)));
+    }
+
+    fColor = vec4(color, 1.0);
 }
\ No newline at end of file
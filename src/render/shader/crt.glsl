@@ -0,0 +1,41 @@
+#type vertex
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aPixelPos;
+
+out vec2 vPixelPos;
+
+void main() {
+    vPixelPos = aPixelPos;
+    gl_Position = vec4(aPos.xyz, 1.0);
+}
+
+#type fragment
+#version 330 core
+// The playfield, drawn offscreen by the chip-8 or software shader.
+uniform sampler2D uTexture;
+uniform float uCrtCurvature;
+uniform float uCrtVignette;
+
+in vec2 vPixelPos;
+
+out vec4 fColor;
+
+void main() {
+    // Bulge the screen out from the middle like a CRT, leaving the corners
+    // that curve off the screen dark.
+    vec2 from_center = vPixelPos * 2.0 - 1.0;
+    from_center *= 1.0 + uCrtCurvature * dot(from_center, from_center);
+    vec2 pixel_pos = from_center * 0.5 + 0.5;
+    if (pixel_pos.x < 0.0 || pixel_pos.x >= 1.0 || pixel_pos.y < 0.0 || pixel_pos.y >= 1.0) {
+        fColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    // The top row of the pixel positions is the top of the framebuffer,
+    // which is the last row of its texture.
+    vec3 color = texture(uTexture, vec2(pixel_pos.x, 1.0 - pixel_pos.y)).rgb;
+    color *= 1.0 - uCrtVignette * dot(from_center, from_center) / 2.0;
+
+    fColor = vec4(color, 1.0);
+}
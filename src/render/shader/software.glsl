@@ -0,0 +1,24 @@
+#type vertex
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aPixelPos;
+
+out vec2 vPixelPos;
+
+void main() {
+    vPixelPos = aPixelPos;
+    gl_Position = vec4(aPos.xyz, 1.0);
+}
+
+#type fragment
+#version 330 core
+// The display already expanded to RGBA on the CPU, top row first.
+uniform sampler2D uTexture;
+
+in vec2 vPixelPos;
+
+out vec4 fColor;
+
+void main() {
+    fColor = texture(uTexture, vPixelPos);
+}
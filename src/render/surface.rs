@@ -1,11 +1,13 @@
+use crate::emulator::{Display, DisplaySink, Palette, GRID_PIXELS_LEN, LOGO_SIZE};
 use crate::render::gl;
-use crate::render::{Buffer, ProgramBuilder, ShaderError, ShaderProgram, VertexArray};
+use crate::render::{Framebuffer, ProgramBuilder, ShaderError, ShaderProgram, Texture, VertexArray};
 use glutin::{
     dpi::{LogicalSize, PhysicalSize},
     event_loop::EventLoop,
-    window::{Window, WindowBuilder},
-    ContextBuilder, ContextWrapper, PossiblyCurrent,
+    window::{Icon, Window, WindowBuilder},
+    Api, ContextBuilder, ContextWrapper, GlProfile, GlRequest, NotCurrent, PossiblyCurrent,
 };
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -20,15 +22,97 @@ pub enum SurfaceError {
     ShaderError(#[from] ShaderError),
 }
 
+/// The Chip-8 display is twice as wide as it is tall.
+const ASPECT_RATIO: (u32, u32) = (2, 1);
+
+/// How the display is scaled to fit a window with a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// Scale to fit inside the window, leaving bars on two sides.
+    Contain,
+    /// Scale to cover the whole window, cropping two sides.
+    Cover,
+    /// Stretch to fill the window.
+    Fill,
+}
+
+impl Fit {
+    /// The viewport (x, y, width, height) the display should be drawn to.
+    pub fn viewport(&self, width: u32, height: u32) -> (i32, i32, u32, u32) {
+        let fit_width = (height * ASPECT_RATIO.0 / ASPECT_RATIO.1).min(width);
+        let fit_height = (width * ASPECT_RATIO.1 / ASPECT_RATIO.0).min(height);
+
+        let (viewport_width, viewport_height) = match self {
+            Fit::Fill => (width, height),
+            Fit::Contain => (fit_width, fit_height),
+            Fit::Cover if fit_width < width => (width, width * ASPECT_RATIO.1 / ASPECT_RATIO.0),
+            Fit::Cover => (height * ASPECT_RATIO.0 / ASPECT_RATIO.1, height),
+        };
+
+        (
+            (width as i32 - viewport_width as i32) / 2,
+            (height as i32 - viewport_height as i32) / 2,
+            viewport_width,
+            viewport_height,
+        )
+    }
+}
+
+/// How the display is turned into pixels on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The chip-8 shader draws every pixel on the GPU, with all of its
+    /// effects.
+    Gl,
+    /// The display is expanded to RGBA on the CPU at SOFTWARE_SCALE, and
+    /// drawn as a plain texture, for GPUs that struggle with the shader. The
+    /// wait indicator and contrast aren't drawn.
+    Software,
+}
+
+/// Each Chip-8 pixel is this many pixels wide in the software backend's
+/// texture.
+pub const SOFTWARE_SCALE: usize = 8;
+
+/// How far the CRT effect bulges the screen, and darkens its corners.
+const CRT_CURVATURE: f32 = 0.08;
+const CRT_VIGNETTE: f32 = 0.4;
+
+/// How far the contrast can be turned down or up.
+const MIN_CONTRAST: f32 = 0.0;
+const MAX_CONTRAST: f32 = 2.0;
+
+/// Each pixel of the logo is drawn this many pixels wide in the icon.
+const ICON_SCALE: usize = 4;
+
+/// The logo from Display::logo as RGBA pixels, with its width and height.
+pub fn logo_icon_rgba(palette: &Palette) -> (Vec<u8>, u32, u32) {
+    let logo = Display::logo();
+    let rgba = logo.to_rgba(palette);
+    let size = LOGO_SIZE * ICON_SCALE;
+
+    let mut icon = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let pixel = ((y / ICON_SCALE) * logo.width() + x / ICON_SCALE) * 4;
+            icon.extend_from_slice(&rgba[pixel..pixel + 4]);
+        }
+    }
+    (icon, size as u32, size as u32)
+}
+
 const TEMP_SHADER: &str = include_str!("./shader/chip-8.glsl");
+const SOFTWARE_SHADER: &str = include_str!("./shader/software.glsl");
+const CRT_SHADER: &str = include_str!("./shader/crt.glsl");
+
+/// The viewport until the window's size is known.
+const DEFAULT_VIEWPORT: (i32, i32, u32, u32) = (0, 0, 640, 320);
 
+/// A window with a Renderer drawing into it. The renderer's methods can be
+/// called on the surface directly.
 pub struct Surface {
     context: ContextWrapper<PossiblyCurrent, Window>,
-    clear_color: (f32, f32, f32),
-    gl: gl::Gl,
-    shader: ShaderProgram,
-    vertex_array: VertexArray,
-    indice_count: usize,
+    renderer: Renderer,
 }
 
 impl Surface {
@@ -36,13 +120,17 @@ impl Surface {
         builder: SurfaceBuilder,
         event_loop: &EventLoop<T>,
     ) -> Result<Self, SurfaceError> {
-        let title = builder.title.unwrap_or("CHIRP-8".to_string());
+        let title = builder.title.as_deref().unwrap_or("CHIRP-8");
         let size = builder.size.unwrap_or((640, 480));
 
-        let context = ContextBuilder::new()
+        let (icon, icon_width, icon_height) = logo_icon_rgba(&builder.palette.unwrap_or(Palette::WHITE));
+
+        let context = builder
+            .context_builder()
             .build_windowed(
                 WindowBuilder::new()
                     .with_title(title)
+                    .with_window_icon(Icon::from_rgba(icon, icon_width, icon_height).ok())
                     .with_inner_size(LogicalSize::new(size.0, size.1)),
                 event_loop,
             )
@@ -53,48 +141,43 @@ impl Surface {
                 .map_err(|_| SurfaceError::ContextCurrent)?
         };
 
+        Self::from_context(builder, context)
+    }
+
+    /// Draws into a window and context made by the host application, rather
+    /// than opening a new window. The context must already be current, and
+    /// support the GL version the shader is written against. The builder's
+    /// window options are ignored.
+    pub fn from_context(
+        builder: SurfaceBuilder,
+        context: ContextWrapper<PossiblyCurrent, Window>,
+    ) -> Result<Self, SurfaceError> {
         let gl = gl::Gl::load_with(|ptr| context.get_proc_address(ptr) as *const _);
+        let mut renderer = Renderer::new(&builder, gl)?;
+        let size = context.window().inner_size();
+        renderer.apply_scaling(size.width, size.height);
 
-        let mut shader = ProgramBuilder::new().with_combo(TEMP_SHADER)?.build(&gl)?;
-        shader.bind();
-        shader.define_uniform("uPixels")?;
-        shader.unbind();
+        Ok(Self { context, renderer })
+    }
 
-        let vertices: [f32; 12] = [
-            -1.0, 1.0, 0.0, // top left
-            -1.0, -1.0, 0.0, // bottom left
-            1.0, -1.0, 0.0, // bottom right
-            1.0, 1.0, 0.0, // top right
-        ];
-        let pixel_pos: [f32; 8] = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0];
-        let indices = [0, 1, 3, 1, 2, 3];
-
-        let vertices_buffer = Buffer::new_array_buffer(&gl, &vertices, 3);
-        let pixel_pos_buffer = Buffer::new_array_buffer(&gl, &pixel_pos, 2);
-        let indices_buffer = Buffer::new_element_buffer(&gl, &indices);
-
-        let mut vertex_array = VertexArray::new(&gl);
-        vertex_array.put_element_buffer(indices_buffer);
-        vertex_array.put_array_buffer(0, vertices_buffer);
-        vertex_array.put_array_buffer(1, pixel_pos_buffer);
-
-        Ok(Self {
-            context,
-            clear_color: (0.0, 0.0, 0.0),
-            gl,
-            shader,
-            vertex_array,
-            indice_count: indices.len(),
-        })
+    pub fn context(&self) -> &ContextWrapper<PossiblyCurrent, Window> {
+        &self.context
     }
 
-    pub fn resize(&self, width: u32, height: u32) {
+    /// The window being drawn into, for changing the cursor, requesting the
+    /// user's attention and the like. Only a shared reference is given out,
+    /// so the window can't be pulled away from its context.
+    pub fn window(&self) -> &Window {
+        self.context.window()
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
         self.context.resize(PhysicalSize::new(width, height));
-        self.gl.set_view_port(0, 0, width, height);
+        self.renderer.apply_scaling(width, height);
     }
 
     pub fn request_redraw(&self) {
-        self.context.window().request_redraw();
+        self.window().request_redraw();
     }
 
     pub fn update(&self) -> Result<(), SurfaceError> {
@@ -102,44 +185,384 @@ impl Surface {
             .swap_buffers()
             .map_err(|_| SurfaceError::SwapBuffers)?;
 
+        self.renderer.clear();
+
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for Surface {
+    type Target = Renderer;
+
+    fn deref(&self) -> &Renderer {
+        &self.renderer
+    }
+}
+
+impl std::ops::DerefMut for Surface {
+    fn deref_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+}
+
+/// The CRT effect, drawn as a second pass over the playfield, which is first
+/// drawn into the offscreen framebuffer at the viewport's size.
+struct CrtPass {
+    shader: ShaderProgram,
+    framebuffer: Framebuffer,
+}
+
+impl CrtPass {
+    fn new(gl: &gl::Gl, (width, height): (u32, u32)) -> Result<Self, ShaderError> {
+        let mut shader = ProgramBuilder::new().with_combo(CRT_SHADER)?.build(gl)?;
+        shader.bind();
+        shader.define_uniform("uCrtCurvature")?;
+        shader.define_uniform("uCrtVignette")?;
+        shader.upload_uniform("uCrtCurvature", &CRT_CURVATURE)?;
+        shader.upload_uniform("uCrtVignette", &CRT_VIGNETTE)?;
+        shader.unbind();
+
+        let framebuffer = Framebuffer::new(gl, width as usize, height as usize);
+        Ok(Self { shader, framebuffer })
+    }
+}
+
+/// Draws the display with whichever Gl it is given, keeping the shader, its
+/// uniforms and the quad they are drawn on. It knows nothing of windows, so
+/// it can be driven by a recording Gl in tests.
+pub struct Renderer {
+    shader_source: String,
+    clear_color: (f32, f32, f32),
+    palette: Palette,
+    grayscale: bool,
+    contrast: f32,
+    crt: Option<CrtPass>,
+    gl: gl::Gl,
+    shader: ShaderProgram,
+    backend: Backend,
+    software_shader: Option<ShaderProgram>,
+    texture: Option<Texture>,
+    vertex_array: VertexArray,
+    wait_indicator: bool,
+    waiting_for_key: bool,
+    flash_reduction: bool,
+    sound_active: bool,
+    depth_buffer: bool,
+    fit: Fit,
+    /// Where the display was last placed in the window.
+    viewport: (i32, i32, u32, u32),
+    created: Instant,
+    upload_throttle: Option<Duration>,
+    uploaded_pixels: Option<[u32; GRID_PIXELS_LEN]>,
+    /// The width and height of the display last given to the shader.
+    grid_size: Option<(usize, usize)>,
+    last_upload: Instant,
+}
+
+impl Renderer {
+    /// Builds the shaders and quad with the builder's rendering options. The
+    /// Gl's context must be current.
+    pub fn new(builder: &SurfaceBuilder, gl: gl::Gl) -> Result<Self, SurfaceError> {
+        let shader = Self::build_shader(&gl, TEMP_SHADER)?;
+
+        let backend = builder.backend.unwrap_or(Backend::Gl);
+        let (software_shader, texture) = match backend {
+            Backend::Gl => (None, None),
+            Backend::Software => (
+                Some(ProgramBuilder::new().with_combo(SOFTWARE_SHADER)?.build(&gl)?),
+                Some(Texture::new(&gl)),
+            ),
+        };
+
+        let vertex_array = VertexArray::new_quad(&gl, (-1.0, 1.0), (1.0, -1.0));
+
+        let mut renderer = Self {
+            shader_source: TEMP_SHADER.to_string(),
+            clear_color: (0.0, 0.0, 0.0),
+            palette: Palette::WHITE,
+            grayscale: builder.grayscale,
+            contrast: 1.0,
+            crt: None,
+            gl,
+            shader,
+            backend,
+            software_shader,
+            texture,
+            vertex_array,
+            wait_indicator: builder.wait_indicator,
+            waiting_for_key: false,
+            flash_reduction: builder.flash_reduction,
+            sound_active: false,
+            depth_buffer: builder.depth_buffer,
+            fit: builder.fit.unwrap_or(Fit::Fill),
+            viewport: DEFAULT_VIEWPORT,
+            created: Instant::now(),
+            upload_throttle: None,
+            uploaded_pixels: None,
+            grid_size: None,
+            last_upload: Instant::now(),
+        };
+        renderer.set_palette(builder.palette.unwrap_or(Palette::WHITE));
+        renderer.set_crt(builder.crt)?;
+        renderer.set_contrast(1.0);
+
+        Ok(renderer)
+    }
+
+    fn build_shader(gl: &gl::Gl, source: &str) -> Result<ShaderProgram, ShaderError> {
+        let mut shader = ProgramBuilder::new().with_combo(source)?.build(gl)?;
+        shader.bind();
+        shader.define_uniform("uPixels")?;
+        shader.define_uniform("uGridSize")?;
+        shader.define_uniform("uWaitIndicator")?;
+        shader.define_uniform("uBackground")?;
+        shader.define_uniform("uForeground")?;
+        shader.define_uniform("uGrayscale")?;
+        shader.define_uniform("uContrast")?;
+        shader.unbind();
+        Ok(shader)
+    }
+
+    /// Replaces the chip-8 shader with one compiled from the given combo
+    /// source, for editing the shader while the emulator runs. It must define
+    /// the same uniforms. If it fails to compile, the old shader is kept.
+    pub fn reload_shader(&mut self, source: &str) -> Result<(), SurfaceError> {
+        self.shader = Self::build_shader(&self.gl, source)?;
+        self.shader_source = source.to_string();
+
+        // The new program starts with all of its uniforms zeroed.
+        self.uploaded_pixels = None;
+        self.grid_size = None;
+        self.set_palette(self.palette);
+        self.set_contrast(self.contrast);
+
+        Ok(())
+    }
+
+    /// The combo source of the chip-8 shader in use, including any reload.
+    /// With the software backend this shader is built but not drawn with.
+    pub fn shader_source(&self) -> &str {
+        &self.shader_source
+    }
+
+    pub fn gl(&self) -> &gl::Gl {
+        &self.gl
+    }
+
+    fn apply_scaling(&mut self, width: u32, height: u32) {
+        self.viewport = self.fit.viewport(width, height);
+        let (x, y, width, height) = self.viewport;
+        self.gl.set_view_port(x, y, width, height);
+        if let Some(crt) = self.crt.as_mut() {
+            crt.framebuffer.resize(width as usize, height as usize);
+        }
+    }
+
+    /// Clears the screen to the palette's background, ready for the next
+    /// frame.
+    pub fn clear(&self) {
         self.gl.set_clear_color(
             self.clear_color.0,
             self.clear_color.1,
             self.clear_color.2,
             1.0,
         );
-        self.gl
-            .clear(&[gl::ClearFlag::COLOR_BUFFER, gl::ClearFlag::DEPTH_BUFFER]);
-
-        Ok(())
+        if self.depth_buffer {
+            self.gl
+                .clear(&[gl::ClearFlag::COLOR_BUFFER, gl::ClearFlag::DEPTH_BUFFER]);
+        } else {
+            self.gl.clear(&[gl::ClearFlag::COLOR_BUFFER]);
+        }
     }
 
     pub fn update_with_display(&mut self, display: &crate::emulator::Display) {
         self.shader.bind();
 
-        self.shader.upload_uniform("uPixels", &display.pixels()).unwrap();
+        // Many ROMs flash the screen along with the beep, so with flash
+        // reduction the screen is held still until the beep stops.
+        if !(self.flash_reduction && self.sound_active) {
+            let grid_size = (display.width(), display.height());
+            if self.grid_size != Some(grid_size) {
+                self.shader.upload_uniform("uGridSize", &(grid_size.0 as f32, grid_size.1 as f32)).unwrap();
+                self.grid_size = Some(grid_size);
+            }
+
+            let pixels = display.grid_pixels();
+            if self.should_upload(&pixels) {
+                match self.texture.as_mut() {
+                    Some(texture) => {
+                        let palette = if self.grayscale { self.palette.to_grayscale() } else { self.palette };
+                        let rgba = display.to_rgba_scaled(&palette, SOFTWARE_SCALE);
+                        texture.upload_rgba(display.width() * SOFTWARE_SCALE, display.height() * SOFTWARE_SCALE, &rgba);
+                    },
+                    None => self.shader.upload_uniform("uPixels", &pixels).unwrap(),
+                }
+                self.uploaded_pixels = Some(pixels);
+                self.last_upload = Instant::now();
+            }
+        }
+
+        let blink = self.created.elapsed().as_millis() % 1000 < 500;
+        let show_wait_indicator = self.wait_indicator && self.waiting_for_key && blink;
+        self.shader.upload_uniform("uWaitIndicator", &(show_wait_indicator as u8 as f32)).unwrap();
 
         self.shader.unbind();
     }
 
-    pub fn render(&self) {
+    /// Uploads the pixels at most this often while they aren't changing, to
+    /// save power on static screens. Changed pixels are always uploaded
+    /// straight away. Off by default, uploading every frame.
+    pub fn set_upload_throttle(&mut self, upload_throttle: Option<Duration>) {
+        self.upload_throttle = upload_throttle;
+    }
+
+    /// The pixels most recently sent to the shader's uPixels uniform, or None
+    /// before the first upload. See Display::grid_pixels for the layout.
+    pub fn last_uploaded_pixels(&self) -> Option<[u32; GRID_PIXELS_LEN]> {
+        self.uploaded_pixels
+    }
+
+    /// The width and height of the display most recently sent to the
+    /// shader's uGridSize uniform.
+    pub fn grid_size(&self) -> Option<(usize, usize)> {
+        self.grid_size
+    }
+
+    fn should_upload(&self, pixels: &[u32; GRID_PIXELS_LEN]) -> bool {
+        match (self.upload_throttle, self.uploaded_pixels) {
+            (Some(throttle), Some(uploaded)) if uploaded == *pixels => self.last_upload.elapsed() >= throttle,
+            _ => true,
+        }
+    }
+
+    /// Scales the difference between the foreground and background colors,
+    /// where 1.0 leaves them as they are. Clamped between MIN_CONTRAST and
+    /// MAX_CONTRAST. Returns the contrast that was set.
+    pub fn set_contrast(&mut self, contrast: f32) -> f32 {
+        self.contrast = contrast.clamp(MIN_CONTRAST, MAX_CONTRAST);
+
         self.shader.bind();
-        self.vertex_array.bind();
-        self.vertex_array.enable_attrib_arrays();
+        self.shader.upload_uniform("uContrast", &self.contrast).unwrap();
+        self.shader.unbind();
 
-        self.gl.draw_elements(self.indice_count);
+        self.contrast
+    }
 
-        self.vertex_array.disable_attrib_arrays();
-        self.vertex_array.unbind();
+    pub fn contrast(&self) -> f32 {
+        self.contrast
+    }
+
+    /// Curves the display like an old CRT screen and darkens its edges, with
+    /// either backend. Turning it on builds the CRT shader and the offscreen
+    /// framebuffer the display is drawn into first, and turning it off drops
+    /// them.
+    pub fn set_crt(&mut self, crt: bool) -> Result<(), SurfaceError> {
+        match (crt, self.crt.is_some()) {
+            (true, false) => {
+                let (_, _, width, height) = self.viewport;
+                self.crt = Some(CrtPass::new(&self.gl, (width, height))?);
+            },
+            (false, true) => self.crt = None,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    pub fn crt(&self) -> bool {
+        self.crt.is_some()
+    }
+
+    /// Changes the colors of the display, including the bars around it.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.clear_color = if self.grayscale { palette.to_grayscale().background } else { palette.background };
+
+        self.shader.bind();
+        self.shader.upload_uniform("uBackground", &palette.background).unwrap();
+        self.shader.upload_uniform("uForeground", &palette.foreground).unwrap();
+        self.shader.upload_uniform("uGrayscale", &(self.grayscale as u8 as f32)).unwrap();
         self.shader.unbind();
+    }
+
+    /// Draws the palette's colors as shades of gray, for grayscale and e-ink
+    /// screens.
+    pub fn set_grayscale(&mut self, grayscale: bool) {
+        self.grayscale = grayscale;
+        self.set_palette(self.palette);
+    }
+
+    /// Whether the emulator is blocked on a key press, shown by the wait
+    /// indicator if it is enabled.
+    pub fn set_waiting_for_key(&mut self, waiting_for_key: bool) {
+        self.waiting_for_key = waiting_for_key;
+    }
+
+    /// Whether the emulator is beeping, which holds the screen still if flash
+    /// reduction is enabled.
+    pub fn set_sound_active(&mut self, sound_active: bool) {
+        self.sound_active = sound_active;
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn render(&self) {
+        match &self.crt {
+            Some(crt) => {
+                let (x, y, width, height) = self.viewport;
+                crt.framebuffer.bind();
+                self.gl.set_view_port(0, 0, width, height);
+                self.draw_playfield();
+                crt.framebuffer.unbind();
+                self.gl.set_view_port(x, y, width, height);
+
+                crt.shader.bind();
+                crt.framebuffer.texture().bind();
+                self.vertex_array.draw();
+                crt.framebuffer.texture().unbind();
+                crt.shader.unbind();
+            },
+            None => self.draw_playfield(),
+        }
 
         self.gl.debug_print_error();
     }
+
+    /// Draws the display with the backend's shader.
+    fn draw_playfield(&self) {
+        let shader = self.software_shader.as_ref().unwrap_or(&self.shader);
+        shader.bind();
+        if let Some(texture) = &self.texture {
+            texture.bind();
+        }
+        self.vertex_array.draw();
+        if let Some(texture) = &self.texture {
+            texture.unbind();
+        }
+        shader.unbind();
+    }
+}
+
+impl DisplaySink for Surface {
+    fn present(&mut self, display: &crate::emulator::Display) {
+        self.update_with_display(display);
+    }
 }
 
 pub struct SurfaceBuilder {
     title: Option<String>,
     size: Option<(u32, u32)>,
+    gl_version: Option<(u8, u8)>,
+    gl_profile: Option<GlProfile>,
+    wait_indicator: bool,
+    flash_reduction: bool,
+    grayscale: bool,
+    crt: bool,
+    depth_buffer: bool,
+    fit: Option<Fit>,
+    palette: Option<Palette>,
+    backend: Option<Backend>,
 }
 
 impl SurfaceBuilder {
@@ -147,6 +570,16 @@ impl SurfaceBuilder {
         Self {
             title: None,
             size: None,
+            gl_version: None,
+            gl_profile: None,
+            wait_indicator: false,
+            flash_reduction: false,
+            grayscale: false,
+            crt: false,
+            depth_buffer: false,
+            fit: None,
+            palette: None,
+            backend: None,
         }
     }
 
@@ -160,7 +593,466 @@ impl SurfaceBuilder {
         self
     }
 
+    /// Requests a specific version of OpenGL. Defaults to 3.3.
+    pub fn with_gl_version(mut self, major: u8, minor: u8) -> Self {
+        self.gl_version = Some((major, minor));
+        self
+    }
+
+    /// Requests a specific OpenGL profile. Defaults to the core profile.
+    pub fn with_gl_profile(mut self, profile: GlProfile) -> Self {
+        self.gl_profile = Some(profile);
+        self
+    }
+
+    /// Blinks a pixel in the corner of the screen while the emulator is
+    /// waiting for a key press, so it doesn't look frozen.
+    pub fn with_wait_indicator(mut self, wait_indicator: bool) -> Self {
+        self.wait_indicator = wait_indicator;
+        self
+    }
+
+    /// Stops the screen from updating while the emulator is beeping, for
+    /// players sensitive to flashing lights.
+    pub fn with_flash_reduction(mut self, flash_reduction: bool) -> Self {
+        self.flash_reduction = flash_reduction;
+        self
+    }
+
+    /// Draws the palette's colors as shades of gray. Off by default.
+    pub fn with_grayscale(mut self, grayscale: bool) -> Self {
+        self.grayscale = grayscale;
+        self
+    }
+
+    /// Curves the display like an old CRT screen. Off by default.
+    pub fn with_crt(mut self, crt: bool) -> Self {
+        self.crt = crt;
+        self
+    }
+
+    /// Allocates and clears a depth buffer every frame. Off by default.
+    pub fn with_depth_buffer(mut self, depth_buffer: bool) -> Self {
+        self.depth_buffer = depth_buffer;
+        self
+    }
+
+    /// How the display is scaled when the window isn't 2:1. Defaults to
+    /// stretching it to fill the window.
+    pub fn with_fit(mut self, fit: Fit) -> Self {
+        self.fit = Some(fit);
+        self
+    }
+
+    /// The colors the display starts with. Defaults to white on black.
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// How the display is drawn. Defaults to the chip-8 shader.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// The context to ask glutin for, with the requested GL version and
+    /// profile.
+    fn context_builder(&self) -> ContextBuilder<'static, NotCurrent> {
+        // The shader is written against GLSL 330 core.
+        let gl_version = self.gl_version.unwrap_or((3, 3));
+        let gl_profile = self.gl_profile.unwrap_or(GlProfile::Core);
+
+        // The emulator only draws a single flat quad, so a depth buffer is
+        // only worth allocating when debugging.
+        let depth_bits = if self.depth_buffer { 24 } else { 0 };
+
+        ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, gl_version))
+            .with_gl_profile(gl_profile)
+            .with_depth_buffer(depth_bits)
+    }
+
     pub fn build<T>(self, event_loop: &EventLoop<T>) -> Result<Surface, SurfaceError> {
         Surface::new(self, event_loop)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::bindings;
+    use crate::render::gl::recording::{self, Call};
+    use crate::emulator::Resolution;
+
+    #[test]
+    fn context_builder_requests_the_gl_version_and_profile() {
+        let context_builder = SurfaceBuilder::new().context_builder();
+        assert!(matches!(context_builder.gl_attr.version, GlRequest::Specific(Api::OpenGl, (3, 3))));
+        assert_eq!(context_builder.gl_attr.profile, Some(GlProfile::Core));
+
+        let context_builder = SurfaceBuilder::new()
+            .with_gl_version(4, 1)
+            .with_gl_profile(GlProfile::Compatibility)
+            .context_builder();
+        assert!(matches!(context_builder.gl_attr.version, GlRequest::Specific(Api::OpenGl, (4, 1))));
+        assert_eq!(context_builder.gl_attr.profile, Some(GlProfile::Compatibility));
+    }
+
+    #[test]
+    fn renderer_builds_the_shader_without_a_window() {
+        let renderer = Renderer::new(&SurfaceBuilder::new(), recording::gl()).unwrap();
+        assert_eq!(renderer.shader_source(), TEMP_SHADER);
+
+        let calls = recording::calls();
+        assert!(calls.iter().any(|call| matches!(call, Call::ShaderSource(source) if source.contains("#version 330 core"))));
+        assert_eq!(recording::uniform_uploads("uForeground"), [vec![1.0, 1.0, 1.0]]);
+    }
+
+    fn recorded_renderer(builder: SurfaceBuilder) -> Renderer {
+        let renderer = Renderer::new(&builder, recording::gl()).unwrap();
+        recording::clear_calls();
+        renderer
+    }
+
+    fn display() -> Display {
+        Display::new(1, (0.0, 0.0, 0.0), (1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn wait_indicator_is_shown_while_waiting_for_a_key() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new().with_wait_indicator(true));
+        renderer.update_with_display(&display());
+        renderer.set_waiting_for_key(true);
+        // The indicator starts in the on half of its blink.
+        renderer.update_with_display(&display());
+        renderer.set_waiting_for_key(false);
+        renderer.update_with_display(&display());
+        assert_eq!(recording::uniform_uploads("uWaitIndicator"), [[0.0], [1.0], [0.0]]);
+
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.set_waiting_for_key(true);
+        renderer.update_with_display(&display());
+        assert_eq!(recording::uniform_uploads("uWaitIndicator"), [[0.0]]);
+    }
+
+    #[test]
+    fn only_the_color_buffer_is_cleared_by_default() {
+        assert_eq!(SurfaceBuilder::new().context_builder().pf_reqs.depth_bits, Some(0));
+        let renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.clear();
+        assert_eq!(recording::calls().last(), Some(&Call::Clear(bindings::COLOR_BUFFER_BIT)));
+
+        let builder = SurfaceBuilder::new().with_depth_buffer(true);
+        assert_eq!(builder.context_builder().pf_reqs.depth_bits, Some(24));
+        let renderer = recorded_renderer(builder);
+        renderer.clear();
+        let both = bindings::COLOR_BUFFER_BIT | bindings::DEPTH_BUFFER_BIT;
+        assert_eq!(recording::calls().last(), Some(&Call::Clear(both)));
+    }
+
+    #[test]
+    fn each_fit_places_the_viewport_in_a_square_window() {
+        // A 2:1 display in a 400x400 window.
+        assert_eq!(Fit::Contain.viewport(400, 400), (0, 100, 400, 200));
+        assert_eq!(Fit::Cover.viewport(400, 400), (-200, 0, 800, 400));
+        assert_eq!(Fit::Fill.viewport(400, 400), (0, 0, 400, 400));
+
+        // And in a window wider than 2:1.
+        assert_eq!(Fit::Contain.viewport(900, 300), (150, 0, 600, 300));
+        assert_eq!(Fit::Cover.viewport(900, 300), (0, -75, 900, 450));
+        assert_eq!(Fit::Fill.viewport(900, 300), (0, 0, 900, 300));
+    }
+
+    #[test]
+    fn the_viewport_follows_the_window_size() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new().with_fit(Fit::Contain));
+        renderer.apply_scaling(400, 400);
+        assert_eq!(recording::calls(), [Call::Viewport(0, 100, 400, 200)]);
+    }
+
+    #[test]
+    fn set_palette_uploads_the_new_colors() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.set_palette(Palette::OCTO);
+        assert_eq!(recording::uniform_uploads("uBackground"), [vec![0.6, 0.4, 0.0]]);
+        assert_eq!(recording::uniform_uploads("uForeground"), [vec![1.0, 0.8, 0.0]]);
+
+        // The bars around the display are cleared to the new background.
+        renderer.clear();
+        assert!(recording::calls().contains(&Call::ClearColor(0.6, 0.4, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn flash_reduction_holds_the_screen_while_beeping() {
+        let mut lit = display();
+        lit.draw_pixel(0, 0);
+
+        let mut renderer = recorded_renderer(SurfaceBuilder::new().with_flash_reduction(true));
+        renderer.update_with_display(&display());
+        renderer.set_sound_active(true);
+        renderer.update_with_display(&lit);
+        assert_eq!(recording::uint_uniform_uploads("uPixels").len(), 1);
+        assert_eq!(renderer.last_uploaded_pixels(), Some(display().grid_pixels()));
+
+        renderer.set_sound_active(false);
+        renderer.update_with_display(&lit);
+        assert_eq!(renderer.last_uploaded_pixels(), Some(lit.grid_pixels()));
+
+        // Without flash reduction the beep changes nothing.
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.set_sound_active(true);
+        renderer.update_with_display(&lit);
+        assert_eq!(recording::uint_uniform_uploads("uPixels"), [lit.grid_pixels().to_vec()]);
+    }
+
+    #[test]
+    fn grayscale_uploads_the_flag_and_clears_to_the_backgrounds_luminance() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.set_grayscale(true);
+        renderer.set_palette(Palette::OCTO);
+        assert_eq!(recording::uniform_uploads("uGrayscale"), [[1.0], [1.0]]);
+        // The colors are still uploaded as they are, for the shader to convert.
+        assert_eq!(recording::uniform_uploads("uBackground").last(), Some(&vec![0.6, 0.4, 0.0]));
+
+        // Weighted the same way as in the shader.
+        let gray = 0.2126 * 0.6 + 0.7152 * 0.4;
+        assert!((crate::emulator::luminance((0.6, 0.4, 0.0)) - gray).abs() < 1e-6);
+        renderer.clear();
+        assert!(recording::calls().contains(&Call::ClearColor(gray, gray, gray, 1.0)));
+
+        renderer.set_grayscale(false);
+        assert_eq!(recording::uniform_uploads("uGrayscale").last(), Some(&vec![0.0]));
+    }
+
+    #[test]
+    fn a_renderer_draws_through_the_gl_it_was_given() {
+        // A host application's own GL, standing in for one loaded from its
+        // context as from_context does.
+        let mut renderer = Renderer::new(&SurfaceBuilder::new(), recording::gl()).unwrap();
+        renderer.update_with_display(&display());
+        renderer.render();
+        assert!(recording::calls().contains(&Call::DrawElements(6)));
+
+        // The host can keep drawing through the same GL.
+        recording::clear_calls();
+        renderer.gl().set_view_port(0, 0, 64, 32);
+        assert_eq!(recording::calls(), [Call::Viewport(0, 0, 64, 32)]);
+    }
+
+    #[test]
+    fn a_static_screen_is_uploaded_at_most_once_per_throttle() {
+        let mut lit = display();
+        lit.draw_pixel(0, 0);
+
+        let throttle = Duration::from_millis(50);
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        renderer.set_upload_throttle(Some(throttle));
+        let started = Instant::now();
+        for _ in 0..5 {
+            renderer.update_with_display(&display());
+        }
+        if started.elapsed() < throttle {
+            assert_eq!(recording::uint_uniform_uploads("uPixels").len(), 1);
+        }
+
+        // A change goes straight up.
+        renderer.update_with_display(&lit);
+        assert_eq!(recording::uint_uniform_uploads("uPixels").last(), Some(&lit.grid_pixels().to_vec()));
+
+        std::thread::sleep(throttle);
+        let uploads = recording::uint_uniform_uploads("uPixels").len();
+        renderer.update_with_display(&lit);
+        renderer.update_with_display(&lit);
+        assert_eq!(recording::uint_uniform_uploads("uPixels").len(), uploads + 1);
+    }
+
+    /// The framebuffer binds, viewports and draws made while rendering a
+    /// frame into a 400x400 window.
+    fn crt_render_calls(builder: SurfaceBuilder) -> Vec<Call> {
+        let mut renderer = Renderer::new(&builder.with_fit(Fit::Contain), recording::gl()).unwrap();
+        renderer.apply_scaling(400, 400);
+        renderer.update_with_display(&display());
+        recording::clear_calls();
+        renderer.render();
+        recording::calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::BindFramebuffer(_) | Call::Viewport(..) | Call::DrawElements(_)))
+            .collect()
+    }
+
+    #[test]
+    fn the_crt_pass_draws_the_offscreen_playfield_only_when_enabled() {
+        let renderer = Renderer::new(&SurfaceBuilder::new().with_crt(true), recording::gl()).unwrap();
+        assert!(renderer.crt());
+        let calls = recording::calls();
+        assert!(calls.iter().any(|call| matches!(call, Call::FramebufferTexture2D(_))));
+        assert!(calls.contains(&Call::TexImage2D(640, 320, None)));
+        assert!(calls.iter().any(|call| matches!(call, Call::ShaderSource(source) if source.contains("uCrtCurvature"))));
+        assert_eq!(recording::uniform_uploads("uCrtCurvature"), [[CRT_CURVATURE]]);
+        assert_eq!(recording::uniform_uploads("uCrtVignette"), [[CRT_VIGNETTE]]);
+
+        for backend in [Backend::Gl, Backend::Software] {
+            let calls = crt_render_calls(SurfaceBuilder::new().with_crt(true).with_backend(backend));
+            let framebuffer = match calls[0] {
+                Call::BindFramebuffer(framebuffer) => framebuffer,
+                ref call => panic!("expected the offscreen framebuffer to be bound first, not {:?}", call),
+            };
+            assert_ne!(framebuffer, 0);
+            // The playfield fills the offscreen framebuffer, which is then
+            // drawn into the window's viewport.
+            let expected = [
+                Call::BindFramebuffer(framebuffer),
+                Call::Viewport(0, 0, 400, 200),
+                Call::DrawElements(6),
+                Call::BindFramebuffer(0),
+                Call::Viewport(0, 100, 400, 200),
+                Call::DrawElements(6),
+            ];
+            assert_eq!(calls, expected);
+        }
+
+        let mut renderer = Renderer::new(&SurfaceBuilder::new().with_fit(Fit::Contain), recording::gl()).unwrap();
+        let calls = recording::calls();
+        assert!(!calls.iter().any(|call| matches!(call, Call::BindFramebuffer(_) | Call::FramebufferTexture2D(_))));
+        assert!(recording::uniform_uploads("uCrtCurvature").is_empty());
+
+        // Turning it on later sizes the framebuffer to the viewport.
+        renderer.apply_scaling(400, 400);
+        renderer.set_crt(true).unwrap();
+        assert!(recording::calls().contains(&Call::TexImage2D(400, 200, None)));
+        renderer.set_crt(false).unwrap();
+        assert!(!renderer.crt());
+
+        assert_eq!(crt_render_calls(SurfaceBuilder::new()), [Call::DrawElements(6)]);
+    }
+
+    #[test]
+    fn contrast_is_uploaded_and_clamped() {
+        // The renderer starts at 1.0, which leaves the colors as they are.
+        let mut renderer = Renderer::new(&SurfaceBuilder::new(), recording::gl()).unwrap();
+        assert_eq!(recording::uniform_uploads("uContrast"), [[1.0]]);
+        assert_eq!(renderer.contrast(), 1.0);
+
+        recording::clear_calls();
+        assert_eq!(renderer.set_contrast(1.5), 1.5);
+        assert_eq!(renderer.set_contrast(5.0), MAX_CONTRAST);
+        assert_eq!(renderer.set_contrast(-1.0), MIN_CONTRAST);
+        assert_eq!(recording::uniform_uploads("uContrast"), [[1.5], [MAX_CONTRAST], [MIN_CONTRAST]]);
+        // Only the contrast changes, not the colors it is applied to.
+        assert!(recording::uniform_uploads("uForeground").is_empty());
+    }
+
+    #[test]
+    fn the_icon_is_the_logo_scaled_up() {
+        let (icon, width, height) = logo_icon_rgba(&Palette::GREEN);
+        let size = LOGO_SIZE * ICON_SCALE;
+        assert_eq!((width, height), (size as u32, size as u32));
+        assert_eq!(icon.len(), size * size * 4);
+
+        let logo = Display::logo();
+        let rgba = logo.to_rgba(&Palette::GREEN);
+        // The C starts at (3, 5).
+        assert!(logo.is_pixel_on(3, 5));
+        let foreground = &rgba[(5 * logo.width() + 3) * 4..][..4];
+        let background = &rgba[..4];
+        assert_ne!(foreground, background);
+
+        for y in 0..size {
+            for x in 0..size {
+                let expected = if logo.is_pixel_on(x / ICON_SCALE, y / ICON_SCALE) { foreground } else { background };
+                assert_eq!(&icon[(y * size + x) * 4..][..4], expected, "icon pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn the_last_uploaded_pixels_are_what_the_shader_was_sent() {
+        let mut checkerboard = display();
+        checkerboard.draw_test_pattern(crate::emulator::TestPattern::Checkerboard);
+
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        assert_eq!(renderer.last_uploaded_pixels(), None);
+        renderer.update_with_display(&checkerboard);
+
+        let uploaded = renderer.last_uploaded_pixels().unwrap();
+        assert_eq!(uploaded, checkerboard.grid_pixels());
+        assert_eq!(recording::uint_uniform_uploads("uPixels"), [uploaded.to_vec()]);
+        // The top-left pixel is lit, and every other one below it.
+        assert_eq!(uploaded[0], 0xAAAA_AAAA);
+    }
+
+    #[test]
+    fn the_software_backend_uploads_the_display_scaled_up() {
+        let mut lit = display();
+        lit.draw_pixel(1, 0);
+
+        let rgba = lit.to_rgba_scaled(&Palette::WHITE, 2);
+        assert_eq!(rgba.len(), 128 * 64 * 4);
+        // Pixel (1, 0) covers (2..4, 0..2) of the scaled image.
+        let is_lit = |x: usize, y: usize| rgba[(y * 128 + x) * 4] == 255;
+        let lit_pixels: Vec<_> = (0..64).flat_map(|y| (0..128).map(move |x| (x, y))).filter(|&(x, y)| is_lit(x, y)).collect();
+        assert_eq!(lit_pixels, [(2, 0), (3, 0), (2, 1), (3, 1)]);
+
+        let mut renderer = recorded_renderer(SurfaceBuilder::new().with_backend(Backend::Software));
+        renderer.update_with_display(&lit);
+        let (width, height) = (64 * SOFTWARE_SCALE, 32 * SOFTWARE_SCALE);
+        let expected = Call::TexImage2D(width as i32, height as i32, Some(lit.to_rgba_scaled(&Palette::WHITE, SOFTWARE_SCALE)));
+        assert!(recording::calls().contains(&expected));
+        // The chip-8 shader isn't sent anything to draw.
+        assert!(recording::uint_uniform_uploads("uPixels").is_empty());
+    }
+
+    #[test]
+    fn shader_source_follows_a_reload() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        let inverted = TEMP_SHADER.replace("fColor = vec4(color, 1.0);", "fColor = vec4(1.0 - color, 1.0);");
+        assert_ne!(inverted, TEMP_SHADER);
+
+        renderer.reload_shader(&inverted).unwrap();
+        assert_eq!(renderer.shader_source(), inverted);
+        assert!(recording::calls().iter().any(|call| matches!(call, Call::ShaderSource(source) if source.contains("1.0 - color"))));
+        // The new program is given the uniforms the old one had.
+        assert_eq!(recording::uniform_uploads("uForeground"), [vec![1.0, 1.0, 1.0]]);
+
+        assert!(renderer.reload_shader("not a shader").is_err());
+        assert_eq!(renderer.shader_source(), inverted);
+    }
+
+    #[test]
+    fn high_resolution_uploads_its_grid_size_and_pixels() {
+        let mut renderer = recorded_renderer(SurfaceBuilder::new());
+        let mut display = display();
+        renderer.update_with_display(&display);
+        assert_eq!(recording::uniform_uploads("uGridSize"), [vec![64.0, 32.0]]);
+
+        display.set_resolution(Resolution::High);
+        display.draw_pixel(127, 63);
+        renderer.update_with_display(&display);
+        assert_eq!(recording::uniform_uploads("uGridSize"), [vec![64.0, 32.0], vec![128.0, 64.0]]);
+        assert_eq!(renderer.grid_size(), Some((128, 64)));
+
+        // The bottom row of the last column is the lowest bit of its second
+        // entry, as the shader reads it.
+        let pixels = recording::uint_uniform_uploads("uPixels").pop().unwrap();
+        assert_eq!(pixels.len(), GRID_PIXELS_LEN);
+        assert_eq!(pixels.iter().position(|&column| column != 0), Some(127 * 2 + 1));
+        assert_eq!(pixels[127 * 2 + 1], 1);
+
+        // The size is only uploaded again when it changes.
+        renderer.update_with_display(&display);
+        assert_eq!(recording::uniform_uploads("uGridSize").len(), 2);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[ignore = "needs a display to open a window on"]
+    fn window_is_opened_at_the_builders_size() {
+        use glutin::platform::unix::EventLoopExtUnix;
+
+        let event_loop = EventLoop::<()>::new_any_thread();
+        let surface = SurfaceBuilder::new().with_size(320, 160).build(&event_loop).unwrap();
+        let size = surface.window().inner_size().to_logical::<u32>(surface.window().scale_factor());
+        assert_eq!((size.width, size.height), (320, 160));
+    }
+}
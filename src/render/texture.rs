@@ -0,0 +1,77 @@
+use crate::render::gl;
+
+/// An RGBA texture sampled without smoothing.
+#[derive(Debug)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+
+    texture_id: gl::TextureId,
+
+    gl: gl::Gl,
+}
+
+impl Texture {
+    pub fn new(gl: &gl::Gl) -> Self {
+        let texture = Texture {
+            width: 0,
+            height: 0,
+            texture_id: gl.create_texture(),
+            gl: gl.clone(),
+        };
+
+        texture.bind();
+        gl.set_texture_nearest_filter();
+        texture.unbind();
+
+        texture
+    }
+
+    /// Replaces the texture's image with 8-bit RGBA pixels, row by row from
+    /// the top left.
+    pub fn upload_rgba(&mut self, width: usize, height: usize, data: &[u8]) {
+        self.width = width;
+        self.height = height;
+
+        self.bind();
+        self.gl.texture_image_rgba(width, height, data);
+        self.unbind();
+    }
+
+    /// Makes room for an RGBA image without uploading any pixels, for drawing
+    /// into through a Framebuffer.
+    pub fn allocate_rgba(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+
+        self.bind();
+        self.gl.texture_image_empty_rgba(width, height);
+        self.unbind();
+    }
+
+    pub fn id(&self) -> &gl::TextureId {
+        &self.texture_id
+    }
+
+    pub fn bind(&self) {
+        self.gl.bind_texture(&self.texture_id);
+    }
+
+    pub fn unbind(&self) {
+        self.gl.unbind_texture();
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.gl.delete_texture(&self.texture_id);
+    }
+}
@@ -28,6 +28,29 @@ impl VertexArray {
         }
     }
 
+    /// A rectangle between two corners in clip space, as two triangles. The
+    /// positions are at location 0, and the position within the rectangle, from
+    /// (0, 0) at the top left to (1, 1) at the bottom right, is at location 1
+    /// for texturing. Quads are built once and can be drawn every frame.
+    pub fn new_quad(gl: &gl::Gl, top_left: (f32, f32), bottom_right: (f32, f32)) -> Self {
+        let (left, top) = top_left;
+        let (right, bottom) = bottom_right;
+        let vertices: [f32; 12] = [
+            left, top, 0.0, // top left
+            left, bottom, 0.0, // bottom left
+            right, bottom, 0.0, // bottom right
+            right, top, 0.0, // top right
+        ];
+        let pixel_pos: [f32; 8] = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0];
+        let indices = [0, 1, 3, 1, 2, 3];
+
+        let mut vertex_array = VertexArray::new(gl);
+        vertex_array.put_element_buffer(Buffer::new_element_buffer(gl, &indices));
+        vertex_array.put_array_buffer(0, Buffer::new_array_buffer(gl, &vertices, 3));
+        vertex_array.put_array_buffer(1, Buffer::new_array_buffer(gl, &pixel_pos, 2));
+        vertex_array
+    }
+
     pub fn put_element_buffer(&mut self, buffer: Buffer) {
         self.put_element_buffer_ref(&Rc::new(buffer));
     }
@@ -65,6 +88,23 @@ impl VertexArray {
         self.array_buffers[location] = Some(buffer.clone());
     }
 
+    /// The number of indices in the element buffer, or 0 without one.
+    pub fn indice_count(&self) -> usize {
+        self.element_buffer.as_ref().map_or(0, |buffer| buffer.len())
+    }
+
+    /// Draws the triangles in the element buffer with whichever shader is
+    /// bound.
+    pub fn draw(&self) {
+        self.bind();
+        self.enable_attrib_arrays();
+
+        self.gl.draw_elements(self.indice_count());
+
+        self.disable_attrib_arrays();
+        self.unbind();
+    }
+
     pub fn bind(&self) {
         self.gl.bind_vertex_array(&self.vertex_array_id);
     }
@@ -89,3 +129,46 @@ impl VertexArray {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::gl::recording::{self, Call};
+
+    /// The vertex array bound and the number of indices drawn when drawing
+    /// a vertex array.
+    fn draw_calls(vertex_array: &VertexArray) -> (u32, i32) {
+        recording::clear_calls();
+        vertex_array.draw();
+        let calls: Vec<_> = recording::calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::BindVertexArray(_) | Call::DrawElements(_)))
+            .collect();
+        match calls[..] {
+            [Call::BindVertexArray(id), Call::DrawElements(count), Call::BindVertexArray(0)] => (id, count),
+            _ => panic!("expected a bound vertex array to be drawn, not {:?}", calls),
+        }
+    }
+
+    #[test]
+    fn quads_are_drawn_independently() {
+        let gl = recording::gl();
+        let playfield = VertexArray::new_quad(&gl, (-1.0, 1.0), (1.0, -1.0));
+        let overlay = VertexArray::new_quad(&gl, (0.5, 1.0), (1.0, 0.5));
+
+        // A triangle reusing the overlay's positions, without building them
+        // again.
+        let mut triangle = VertexArray::new(&gl);
+        triangle.put_element_buffer(Buffer::new_element_buffer(&gl, &[0, 1, 2]));
+        triangle.put_array_buffer_ref(0, overlay.array_buffers[0].as_ref().unwrap());
+        assert_eq!(Rc::strong_count(overlay.array_buffers[0].as_ref().unwrap()), 2);
+
+        let (playfield_id, playfield_count) = draw_calls(&playfield);
+        let (overlay_id, overlay_count) = draw_calls(&overlay);
+        let (triangle_id, triangle_count) = draw_calls(&triangle);
+        assert_eq!((playfield_count, overlay_count, triangle_count), (6, 6, 3));
+        assert_ne!(playfield_id, overlay_id);
+        assert_ne!(overlay_id, triangle_id);
+        assert_eq!(draw_calls(&playfield), (playfield_id, 6));
+    }
+}